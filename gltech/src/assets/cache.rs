@@ -0,0 +1,332 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    fmt,
+    ops::Deref,
+    sync::{Arc, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    time::SystemTime,
+};
+
+use thiserror::Error;
+
+use crate::{Image, Texture, assets::*};
+
+#[derive(Error, Debug)]
+pub enum AssetError {
+    #[error(transparent)]
+    Vfs(#[from] VfsError),
+
+    #[error("Failed to decode \"{path}\": {message}")]
+    Decode { path: String, message: String },
+}
+
+pub type AssetResult<T> = Result<T, AssetError>;
+
+/// A type that can be loaded from the virtual filesystem and cached by [`Assets`].
+pub trait Asset: Send + Sync + Sized + 'static {
+    fn load(assets: &Assets, path: &str) -> AssetResult<Self>;
+
+    /// Whether the asset's data is still used outside of its handles, for example an image shared
+    /// with a texture through [`Image::cheap_clone`]. Shared assets are never freed.
+    fn is_shared(&self) -> bool {
+        false
+    }
+}
+
+/// A reference to a cached asset. Cloning a handle is cheap and shares the asset.
+pub struct Handle<T> {
+    path: Arc<str>,
+    asset: Arc<T>,
+}
+
+impl<T> Handle<T> {
+    /// The normalized path the asset was loaded from.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Whether two handles refer to the same asset.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.asset, &other.asset)
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            path: self.path.clone(),
+            asset: self.asset.clone(),
+        }
+    }
+}
+
+impl<T> Deref for Handle<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.asset
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Handle({:?})", self.path)
+    }
+}
+
+struct Entry {
+    path: Arc<str>,
+    asset: Arc<dyn Any + Send + Sync>,
+    /// When the asset's file was last modified, if it is a loose file.
+    modified: Option<SystemTime>,
+    /// Whether only the cache refers to the asset.
+    unused: fn(&Entry) -> bool,
+}
+
+fn unused<T: Asset>(entry: &Entry) -> bool {
+    Arc::strong_count(&entry.asset) == 1
+        && !entry.asset.downcast_ref::<T>().is_some_and(T::is_shared)
+}
+
+struct Inner {
+    vfs: RwLock<Vfs>,
+    cache: Mutex<HashMap<(TypeId, String), Entry>>,
+}
+
+/// A cache of assets loaded from a [`Vfs`], keyed by type and path.
+///
+/// Loading the same path twice returns the same asset. Assets stay cached until
+/// [`free_unused`](Self::free_unused) finds them unreferenced, which the engine does whenever the
+/// scene changes. `Assets` is cheap to clone, and clones share the same cache.
+#[derive(Clone)]
+pub struct Assets {
+    inner: Arc<Inner>,
+}
+
+impl Assets {
+    pub fn new(vfs: Vfs) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                vfs: RwLock::new(vfs),
+                cache: Mutex::new(HashMap::new()),
+            }),
+        }
+    }
+
+    pub fn vfs(&self) -> RwLockReadGuard<'_, Vfs> {
+        self.inner.vfs.read().unwrap()
+    }
+
+    /// Gives access to the filesystem to mount more packs or directories.
+    pub fn vfs_mut(&self) -> RwLockWriteGuard<'_, Vfs> {
+        self.inner.vfs.write().unwrap()
+    }
+
+    /// Loads an asset, or returns the cached one if it was already loaded.
+    pub fn load<T: Asset>(&self, path: &str) -> AssetResult<Handle<T>> {
+        let key = (TypeId::of::<T>(), normalize_path(path));
+        if let Some(handle) = self.cached(&key) {
+            return Ok(handle);
+        }
+
+        // Decode without holding the lock, so other threads can use the cache meanwhile. If
+        // another thread loaded the same asset in the meantime, its copy wins.
+        let asset = Arc::new(T::load(self, path)?);
+        let modified = self.vfs().modified(path);
+        let mut cache = self.inner.cache.lock().unwrap();
+        let entry = cache.entry(key).or_insert_with_key(|(_, path)| Entry {
+            path: path.as_str().into(),
+            asset,
+            modified,
+            unused: unused::<T>,
+        });
+        Ok(Self::handle(entry))
+    }
+
+    /// Starts a list of assets to load on a background thread, such as
+    /// `assets.batch().load::<Texture>("wall.png").load::<Sound>("door.wav").start()`.
+    pub fn batch(&self) -> LoadBatch {
+        LoadBatch::new(self.clone())
+    }
+
+    /// Returns an asset if it is already loaded, without loading it.
+    pub fn get<T: Asset>(&self, path: &str) -> Option<Handle<T>> {
+        self.cached(&(TypeId::of::<T>(), normalize_path(path)))
+    }
+
+    fn cached<T: Asset>(&self, key: &(TypeId, String)) -> Option<Handle<T>> {
+        self.inner.cache.lock().unwrap().get(key).map(Self::handle)
+    }
+
+    fn handle<T: Asset>(entry: &Entry) -> Handle<T> {
+        Handle {
+            path: entry.path.clone(),
+            asset: entry.asset.clone().downcast().unwrap(),
+        }
+    }
+
+    /// Drops every cached asset that nothing else refers to. Returns how many were freed.
+    pub fn free_unused(&self) -> usize {
+        let mut cache = self.inner.cache.lock().unwrap();
+        let mut freed = 0;
+
+        // Freeing a texture can leave its image unused, so repeat until nothing changes
+        loop {
+            let before = cache.len();
+            cache.retain(|_, entry| !(entry.unused)(entry));
+            if cache.len() == before {
+                return freed;
+            }
+            freed += before - cache.len();
+        }
+    }
+
+    /// Re-decodes cached images whose loose files changed since they were loaded. Images are
+    /// updated in place, so planes showing them change on the next frame. Planes and textures keep
+    /// their own copies of an image's dimensions, so an image whose size changed can't be resized
+    /// and is stretched to its old size instead. Returns how many images were reloaded.
+    pub fn reload_changed(&self) -> usize {
+        let changed: Vec<(String, Handle<Image>)> = {
+            let vfs = self.vfs();
+            let mut cache = self.inner.cache.lock().unwrap();
+            cache
+                .iter_mut()
+                .filter(|((type_id, _), _)| *type_id == TypeId::of::<Image>())
+                .filter_map(|((_, path), entry)| {
+                    let modified = vfs.modified(path);
+                    if modified == entry.modified {
+                        return None;
+                    }
+                    entry.modified = modified;
+                    Some((path.clone(), Self::handle(entry)))
+                })
+                .collect()
+        };
+
+        // Decode without holding the locks, the same way load does
+        let mut reloaded = 0;
+        for (path, image) in changed {
+            let bytes = self.vfs().read(&path).map_err(|e| e.to_string());
+            let decoded = match bytes.and_then(|bytes| Image::decode(&bytes)) {
+                Ok(decoded) => decoded,
+                Err(e) => {
+                    eprintln!("Failed to reload \"{}\": {}", path, e);
+                    continue;
+                }
+            };
+            if decoded.dimensions() != image.dimensions() {
+                eprintln!(
+                    "\"{}\" changed size from {:?} to {:?} and is stretched to its old size",
+                    path,
+                    image.dimensions(),
+                    decoded.dimensions()
+                );
+            }
+            image.copy_stretched_from(&decoded);
+            reloaded += 1;
+
+            // Textures share their source image with the cache, but their mips need rebuilding
+            if let Some(texture) = self.cached::<Texture>(&(TypeId::of::<Texture>(), path)) {
+                texture.rebuild_mips();
+            }
+        }
+        reloaded
+    }
+
+    /// The number of cached assets.
+    pub fn len(&self) -> usize {
+        self.inner.cache.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for Assets {
+    fn default() -> Self {
+        Self::new(Vfs::new())
+    }
+}
+
+impl Asset for Image {
+    fn load(assets: &Assets, path: &str) -> AssetResult<Self> {
+        let bytes = assets.vfs().read(path)?;
+        Image::decode(&bytes).map_err(|message| AssetError::Decode {
+            path: path.into(),
+            message,
+        })
+    }
+
+    fn is_shared(&self) -> bool {
+        self.shares_pixels()
+    }
+}
+
+/// Textures are created from the cached image at the same path, so the image is only decoded
+/// once.
+impl Asset for Texture {
+    fn load(assets: &Assets, path: &str) -> AssetResult<Self> {
+        let image = assets.load::<Image>(path)?;
+        Ok(Texture::new(image.cheap_clone()))
+    }
+
+    fn is_shared(&self) -> bool {
+        self.shares_frames()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::{Color, assets::testing::TestDir};
+
+    #[test]
+    fn loads_are_cached_until_unused() {
+        let dir = TestDir::new("assets");
+        dir.save_image("wall.png", &Image::new(4, 2));
+        let assets = dir.assets();
+
+        let first = assets.load::<Texture>("Wall.png").unwrap();
+        let second = assets.load::<Texture>("wall.png").unwrap();
+        assert!(first.ptr_eq(&second));
+        assert_eq!(first.source().dimensions(), (4, 2));
+        assert_eq!(assets.len(), 2);
+
+        // A plane's copy of the texture keeps it, and its image, alive
+        let copy = first.cheap_clone();
+        drop((first, second));
+        assert_eq!(assets.free_unused(), 0);
+
+        drop(copy);
+        assert_eq!(assets.free_unused(), 2);
+        assert!(assets.is_empty());
+    }
+
+    #[test]
+    fn reloads_changed_images_in_place() {
+        let dir = TestDir::new("reload");
+        dir.save_image("wall.png", &Image::new(2, 2));
+        let assets = dir.assets();
+        let texture = assets.load::<Texture>("wall.png").unwrap();
+        assert_eq!(assets.reload_changed(), 0);
+
+        let changed = Image::new(4, 4);
+        for (x, y) in changed.coordinates() {
+            changed.set(x, y, Color::WHITE);
+        }
+        dir.save_image("wall.png", &changed);
+        let file = fs::File::options()
+            .write(true)
+            .open(dir.join("wall.png"))
+            .unwrap();
+        file.set_modified(SystemTime::now() + std::time::Duration::from_secs(1))
+            .unwrap();
+
+        assert_eq!(assets.reload_changed(), 1);
+        assert_eq!(texture.source().dimensions(), (2, 2));
+        assert_eq!(texture.source().get(1, 1).r(), 255);
+    }
+}
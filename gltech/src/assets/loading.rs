@@ -0,0 +1,142 @@
+use std::{
+    any::Any,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use crate::assets::*;
+
+type Job = Box<dyn FnOnce(&Assets) -> AssetResult<Box<dyn Any + Send>> + Send>;
+
+/// A list of assets to load in the background. Created by [`Assets::batch`].
+pub struct LoadBatch {
+    assets: Assets,
+    jobs: Vec<Job>,
+}
+
+impl LoadBatch {
+    pub(crate) fn new(assets: Assets) -> Self {
+        Self {
+            assets,
+            jobs: Vec::new(),
+        }
+    }
+
+    pub fn load<T: Asset>(mut self, path: impl Into<String>) -> Self {
+        let path = path.into();
+        self.jobs.push(Box::new(move |assets| {
+            let handle: Box<dyn Any + Send> = Box::new(assets.load::<T>(&path)?);
+            Ok(handle)
+        }));
+        self
+    }
+
+    /// Starts loading on a background thread. Assets are loaded one at a time, so loading
+    /// doesn't compete with the renderer for cores.
+    pub fn start(self) -> Loading {
+        let progress = Arc::new(Progress {
+            total: self.jobs.len(),
+            finished: AtomicUsize::new(0),
+            handles: Mutex::new(Vec::new()),
+            errors: Mutex::new(Vec::new()),
+        });
+
+        let shared = progress.clone();
+        std::thread::Builder::new()
+            .name("gltech-loader".into())
+            .spawn(move || {
+                for job in self.jobs {
+                    match job(&self.assets) {
+                        Ok(handle) => shared.handles.lock().unwrap().push(handle),
+                        Err(e) => shared.errors.lock().unwrap().push(e),
+                    }
+                    shared.finished.fetch_add(1, Ordering::Release);
+                }
+            })
+            .expect("Failed to spawn the asset loading thread");
+
+        Loading { progress }
+    }
+}
+
+struct Progress {
+    total: usize,
+    finished: AtomicUsize,
+    handles: Mutex<Vec<Box<dyn Any + Send>>>,
+    errors: Mutex<Vec<AssetError>>,
+}
+
+/// Assets loading in the background. Poll it every frame, for example to draw a progress bar,
+/// and build the next scene once it is done.
+///
+/// Loaded assets are kept alive until the `Loading` is dropped, so they are still cached when the
+/// next scene is built even if the scene changes meanwhile.
+pub struct Loading {
+    progress: Arc<Progress>,
+}
+
+impl Loading {
+    #[inline]
+    pub fn total(&self) -> usize {
+        self.progress.total
+    }
+
+    /// How many assets finished loading, successfully or not.
+    #[inline]
+    pub fn finished(&self) -> usize {
+        self.progress.finished.load(Ordering::Acquire)
+    }
+
+    /// The fraction of assets that finished loading, from 0 to 1.
+    pub fn progress(&self) -> f32 {
+        match self.total() {
+            0 => 1.0,
+            total => self.finished() as f32 / total as f32,
+        }
+    }
+
+    #[inline]
+    pub fn is_done(&self) -> bool {
+        self.finished() == self.total()
+    }
+
+    /// Takes the errors of the assets that failed to load so far.
+    pub fn take_errors(&self) -> Vec<AssetError> {
+        std::mem::take(&mut self.progress.errors.lock().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::{Image, Texture, assets::testing::TestDir};
+
+    #[test]
+    fn loads_in_the_background() {
+        let dir = TestDir::new("loading");
+        dir.save_image("wall.png", &Image::new(2, 2));
+        let assets = dir.assets();
+        let loading = assets
+            .batch()
+            .load::<Texture>("wall.png")
+            .load::<Image>("missing.png")
+            .start();
+
+        let start = Instant::now();
+        while !loading.is_done() {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            std::thread::yield_now();
+        }
+        assert_eq!(loading.progress(), 1.0);
+        assert_eq!(loading.take_errors().len(), 1);
+
+        // The loaded texture stays cached while the loading is alive
+        assert_eq!(assets.free_unused(), 0);
+        assert!(assets.get::<Texture>("wall.png").is_some());
+        drop(loading);
+        assert_eq!(assets.free_unused(), 2);
+    }
+}
@@ -0,0 +1,369 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    Background, Color, Empty, Entity, Image, Params, Plane, Scene, ScriptRegistry, Sky, Texture,
+    Vector, assets::*, standard::Billboard,
+};
+
+#[derive(Error, Debug)]
+pub enum MapError {
+    #[error("Invalid map: {0}")]
+    Parse(#[from] ron::error::SpannedError),
+
+    #[error("Failed to write map: {0}")]
+    Write(#[from] ron::Error),
+
+    #[error("Failed to save map: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Asset(#[from] AssetError),
+
+    #[error("Failed to create script \"{name}\": {message}")]
+    Script { name: String, message: String },
+
+    #[error("Scene doesn't match the map: {0}")]
+    Mismatch(String),
+}
+
+/// A scene described in a text map file, in [RON](https://github.com/ron-rs/ron).
+///
+/// Positions are `(x, y)` pairs and angles are in degrees. Textures are paths in the asset
+/// filesystem, and scripts are attached by name with optional parameters. Every field can be
+/// left out.
+///
+/// ```ron
+/// (
+///     camera: (pos: (0, 0), angle: 0, fov: 90),
+///     background: Sky("sky.png"),
+///     planes: [
+///         (start: (100, -25), end: (100, 25), texture: "wall.png", texel_density: 0.5),
+///         (
+///             start: (150, -25),
+///             end: (150, 25),
+///             texture: "door.png",
+///             scripts: [(name: "rotate", params: {"speed": 9})],
+///         ),
+///     ],
+///     sprites: [(pos: (50, 10), width: 20, texture: "barrel.png")],
+///     empties: [(pos: (0, 0), angle: 0, scripts: [(name: "q1_controller")])],
+/// )
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Map {
+    pub camera: MapCamera,
+    pub background: MapBackground,
+    pub planes: Vec<MapPlane>,
+    /// Planes that always face the camera.
+    pub sprites: Vec<MapSprite>,
+    /// Invisible entities that only carry scripts.
+    pub empties: Vec<MapEmpty>,
+}
+
+/// Where the main camera starts.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MapCamera {
+    pub pos: (f32, f32),
+    pub angle: f32,
+    pub fov: f32,
+}
+
+impl Default for MapCamera {
+    fn default() -> Self {
+        Self {
+            pos: (0.0, 0.0),
+            angle: 0.0,
+            fov: 90.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum MapBackground {
+    Color(u8, u8, u8),
+    /// A sky image, see [`Sky`].
+    Sky(String),
+}
+
+impl Default for MapBackground {
+    fn default() -> Self {
+        MapBackground::Color(0, 0, 0)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MapPlane {
+    pub start: (f32, f32),
+    pub end: (f32, f32),
+    pub texture: String,
+    /// See [`Texture::set_texel_density`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub texel_density: Option<f32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scripts: Vec<MapScript>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MapSprite {
+    /// The center of the sprite.
+    pub pos: (f32, f32),
+    pub width: f32,
+    pub texture: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scripts: Vec<MapScript>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MapEmpty {
+    pub pos: (f32, f32),
+    #[serde(default)]
+    pub angle: f32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scripts: Vec<MapScript>,
+}
+
+/// A script attached to an entity by the name it was registered under.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MapScript {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Params::is_empty")]
+    pub params: Params,
+}
+
+impl Map {
+    pub fn parse(source: &str) -> Result<Self, MapError> {
+        Ok(options().from_str(source)?)
+    }
+
+    /// Writes the map back to text.
+    pub fn to_source(&self) -> Result<String, MapError> {
+        let config = ron::ser::PrettyConfig::new().struct_names(false);
+        Ok(options().to_string_pretty(self, config)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), MapError> {
+        Ok(std::fs::write(path, self.to_source()?)?)
+    }
+
+    /// Builds a scene from the map, loading textures through `assets` and creating attached
+    /// scripts through `scripts`.
+    pub fn build(&self, assets: &Assets, scripts: &ScriptRegistry) -> Result<Scene, MapError> {
+        let attach = |entity: &mut Entity, attached: &[MapScript]| -> Result<(), MapError> {
+            for script in attached {
+                let created = scripts.create(&script.name, &script.params);
+                let created = created.map_err(|message| MapError::Script {
+                    name: script.name.clone(),
+                    message,
+                })?;
+                entity.add_script(created);
+            }
+            Ok(())
+        };
+
+        let mut scene = Scene::new();
+        scene.camera.set_pos(self.camera.pos);
+        scene.camera.set_dir(Vector::from_deg(self.camera.angle));
+        scene.camera.fov = self.camera.fov;
+        scene.background = match &self.background {
+            MapBackground::Color(r, g, b) => Background::Color(Color::rgb(*r, *g, *b)),
+            MapBackground::Sky(path) => {
+                Background::Sky(Sky::new(assets.load::<Image>(path)?.cheap_clone()))
+            }
+        };
+
+        for plane in &self.planes {
+            let mut texture = assets.load::<Texture>(&plane.texture)?.cheap_clone();
+            texture.set_texel_density(plane.texel_density);
+            let start = Vector::from(plane.start);
+            let mut entity =
+                Entity::from(Plane::new(start, Vector::from(plane.end) - start, texture));
+            attach(&mut entity, &plane.scripts)?;
+            scene.add(entity);
+        }
+
+        for sprite in &self.sprites {
+            let texture = assets.load::<Texture>(&sprite.texture)?.cheap_clone();
+            let dir = Vector::RIGHT * sprite.width;
+            let start = Vector::from(sprite.pos) - dir / 2.0;
+            let mut entity = Entity::from(Plane::new(start, dir, texture));
+            entity.add_script(Box::new(Billboard::new(sprite.width)));
+            attach(&mut entity, &sprite.scripts)?;
+            scene.add(entity);
+        }
+
+        for empty in &self.empties {
+            let mut entity = Entity::from(Empty {
+                pos: empty.pos.into(),
+                dir: Vector::from_deg(empty.angle),
+            });
+            attach(&mut entity, &empty.scripts)?;
+            scene.add(entity);
+        }
+
+        Ok(scene)
+    }
+
+    /// A copy of the map with the camera and every entity moved to where they are in `scene`,
+    /// which must have been built from this map, such as after editing it in game.
+    ///
+    /// Scenes don't record which files their textures came from or how their scripts were
+    /// created, so textures and scripts are kept from this map. Entities are matched by the order
+    /// [`build`](Self::build) adds them in.
+    pub fn capture(&self, scene: &Scene) -> Result<Self, MapError> {
+        let expected = self.planes.len() + self.sprites.len() + self.empties.len();
+        let found = scene.entities().count();
+        if found != expected {
+            return Err(MapError::Mismatch(format!(
+                "the map has {} entities, but the scene has {}",
+                expected, found
+            )));
+        }
+
+        let mut map = self.clone();
+        map.camera = MapCamera {
+            pos: scene.camera.pos().into(),
+            angle: scene.camera.angle(),
+            fov: scene.camera.fov,
+        };
+
+        let mut entities = scene.entities();
+        for (plane, entity) in map.planes.iter_mut().zip(&mut entities) {
+            plane.start = entity.pos().into();
+            plane.end = (entity.pos() + entity.dir()).into();
+        }
+        for (sprite, entity) in map.sprites.iter_mut().zip(&mut entities) {
+            sprite.pos = (entity.pos() + entity.dir() / 2.0).into();
+        }
+        for (empty, entity) in map.empties.iter_mut().zip(&mut entities) {
+            empty.pos = entity.pos().into();
+            empty.angle = entity.angle();
+        }
+        Ok(map)
+    }
+}
+
+/// Optional values are written without `Some(...)`.
+fn options() -> ron::Options {
+    ron::Options::default().with_default_extension(ron::extensions::Extensions::IMPLICIT_SOME)
+}
+
+impl Asset for Map {
+    fn load(assets: &Assets, path: &str) -> AssetResult<Self> {
+        let bytes = assets.vfs().read(path)?;
+        String::from_utf8(bytes)
+            .map_err(|e| e.to_string())
+            .and_then(|source| Map::parse(&source).map_err(|e| e.to_string()))
+            .map_err(|message| AssetError::Decode {
+                path: path.into(),
+                message,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+    use crate::assets::testing::{Idle, TestDir};
+
+    const SOURCE: &str = r#"(
+        camera: (pos: (10, -5), angle: 90),
+        background: Color(20, 30, 40),
+        planes: [
+            (start: (100, -25), end: (100, 25), texture: "wall.png", texel_density: 0.5),
+            (
+                start: (150, -25),
+                end: (150, 25),
+                texture: "wall.png",
+                scripts: [(name: "spin", params: {"speed": 9, "reverse": true, "label": "door"})],
+            ),
+        ],
+        sprites: [(pos: (50, 10), width: 20, texture: "wall.png")],
+        empties: [(pos: (0, 0), scripts: [(name: "spin")])],
+    )"#;
+
+    #[test]
+    fn round_trips_through_text() {
+        let map = Map::parse(SOURCE).unwrap();
+        assert_eq!(map.camera.fov, 90.0);
+        assert_eq!(map.planes[0].texel_density, Some(0.5));
+        let params = &map.planes[1].scripts[0].params;
+        assert_eq!(params["speed"].as_f32(), Some(9.0));
+        assert_eq!(params["reverse"].as_bool(), Some(true));
+        assert_eq!(params["label"].as_str(), Some("door"));
+
+        let saved = map.to_source().unwrap();
+        assert_eq!(Map::parse(&saved).unwrap(), map);
+        assert_eq!(Map::parse("()").unwrap(), Map::default());
+    }
+
+    #[test]
+    fn builds_a_scene() {
+        let dir = TestDir::new("map");
+        dir.save_image("wall.png", &Image::new(2, 2));
+        let assets = dir.assets();
+
+        let created = Rc::new(RefCell::new(Vec::new()));
+        let mut scripts = ScriptRegistry::new();
+        let spun = created.clone();
+        scripts.register("spin", move |params| {
+            spun.borrow_mut().push(params.len());
+            Ok(Box::new(Idle))
+        });
+        let mut scene = Map::parse(SOURCE)
+            .unwrap()
+            .build(&assets, &scripts)
+            .unwrap();
+
+        assert_eq!(scene.planes().count(), 3);
+        assert_eq!(scene.entities_mut().count(), 4);
+        assert_eq!(*created.borrow(), vec![3, 0]);
+        assert_eq!(scene.camera.pos().x(), 10.0);
+        assert!(matches!(scene.background, Background::Color(_)));
+
+        let unknown = Map::parse(r#"(empties: [(pos: (0, 0), scripts: [(name: "nope")])])"#)
+            .unwrap()
+            .build(&assets, &scripts);
+        assert!(matches!(unknown, Err(MapError::Script { .. })));
+    }
+
+    #[test]
+    fn saves_an_edited_scene() {
+        let dir = TestDir::new("map_capture");
+        dir.save_image("wall.png", &Image::new(2, 2));
+        let assets = dir.assets();
+        let mut scripts = ScriptRegistry::new();
+        scripts.register("spin", |_| Ok(Box::new(Idle)));
+
+        let map = Map::parse(SOURCE).unwrap();
+        let mut scene = map.build(&assets, &scripts).unwrap();
+        scene.camera.set_pos((30.0, 40.0));
+        let mut entities: Vec<&mut Entity> = scene.entities_mut().collect();
+        entities[0].translate((0.0, 10.0));
+        entities[3].set_angle(90.0);
+
+        let captured = map.capture(&scene).unwrap();
+        let saved = Map::parse(&captured.to_source().unwrap()).unwrap();
+        assert_eq!(saved, captured);
+        assert_eq!(saved.camera.pos, (30.0, 40.0));
+        assert_eq!(saved.planes[0].start, (100.0, -15.0));
+        assert_eq!(saved.planes[0].end, (100.0, 35.0));
+        assert_eq!(saved.sprites[0].pos, (50.0, 10.0));
+        assert_eq!(saved.empties[0].angle, 90.0);
+        assert_eq!(saved.planes[1..], map.planes[1..]);
+
+        // Building the saved map gives the edited scene back
+        let rebuilt = saved.build(&assets, &scripts).unwrap();
+        assert_eq!(saved.capture(&rebuilt).unwrap(), saved);
+        assert!(matches!(
+            Map::default().capture(&scene),
+            Err(MapError::Mismatch(_))
+        ));
+    }
+}
@@ -0,0 +1,17 @@
+mod cache;
+mod loading;
+mod map;
+mod sound;
+#[cfg(test)]
+pub(crate) mod testing;
+mod tiled;
+mod vfs;
+mod wad;
+
+pub use cache::*;
+pub use loading::*;
+pub use map::*;
+pub use sound::*;
+pub use tiled::*;
+pub use vfs::*;
+pub use wad::*;
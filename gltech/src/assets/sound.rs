@@ -0,0 +1,72 @@
+use std::{io::Cursor, sync::Arc};
+
+use hound::{SampleFormat, WavReader};
+
+use crate::assets::*;
+
+/// A decoded sound, as interleaved samples from -1 to 1.
+pub struct Sound {
+    sample_rate: u32,
+    channels: u16,
+    samples: Arc<[f32]>,
+}
+
+impl Sound {
+    /// Decodes a WAV file with integer or floating point samples.
+    pub fn decode(bytes: &[u8]) -> Result<Self, String> {
+        let reader = WavReader::new(Cursor::new(bytes)).map_err(|e| e.to_string())?;
+        let spec = reader.spec();
+        let samples = match spec.sample_format {
+            SampleFormat::Float => reader.into_samples::<f32>().collect::<Result<_, _>>(),
+            SampleFormat::Int => {
+                let scale = 1.0 / (1u32 << (spec.bits_per_sample - 1)) as f32;
+                reader
+                    .into_samples::<i32>()
+                    .map(|sample| sample.map(|sample| sample as f32 * scale))
+                    .collect()
+            }
+        }
+        .map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            sample_rate: spec.sample_rate,
+            channels: spec.channels,
+            samples,
+        })
+    }
+
+    #[inline]
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    #[inline]
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    /// The samples of every channel, interleaved.
+    #[inline]
+    pub fn samples(&self) -> &[f32] {
+        &self.samples
+    }
+
+    /// The number of samples per channel.
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels.max(1) as usize
+    }
+
+    pub fn duration(&self) -> std::time::Duration {
+        std::time::Duration::from_secs_f64(self.frames() as f64 / self.sample_rate.max(1) as f64)
+    }
+}
+
+impl Asset for Sound {
+    fn load(assets: &Assets, path: &str) -> AssetResult<Self> {
+        let bytes = assets.vfs().read(path)?;
+        Sound::decode(&bytes).map_err(|message| AssetError::Decode {
+            path: path.into(),
+            message,
+        })
+    }
+}
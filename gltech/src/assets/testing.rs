@@ -0,0 +1,57 @@
+//! Fixtures shared by tests that need files on disk.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{EndContext, Image, Script, StartContext, UpdateContext, assets::*};
+
+/// A temporary directory that is removed when dropped, even if the test fails.
+pub(crate) struct TestDir(PathBuf);
+
+impl TestDir {
+    pub(crate) fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("gltech_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// A path inside the directory, with its parent directories created.
+    pub(crate) fn join(&self, path: &str) -> PathBuf {
+        let path = self.0.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        path
+    }
+
+    pub(crate) fn save_image(&self, path: &str, image: &Image) {
+        image.save(self.join(path)).unwrap();
+    }
+
+    /// An asset cache with the directory as its only mount.
+    pub(crate) fn assets(&self) -> Assets {
+        let mut vfs = Vfs::new();
+        vfs.mount_dir(&self.0, 0).unwrap();
+        Assets::new(vfs)
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// A script that does nothing, for tests that only check scripts get attached.
+pub(crate) struct Idle;
+
+impl Script for Idle {
+    fn start(&mut self, _ctx: StartContext) {}
+    fn tick(&mut self, _ctx: UpdateContext) {}
+    fn end(&mut self, _ctx: EndContext) {}
+}
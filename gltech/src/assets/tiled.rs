@@ -0,0 +1,548 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::{
+    Empty, Entity, GridBuilder, Image, Param, Params, Plane, Scene, ScriptRegistry, Texture,
+    TileGrid, Vector, assets::*, standard::Billboard,
+};
+
+#[derive(Error, Debug)]
+pub enum TiledError {
+    #[error("Invalid Tiled map: {0}")]
+    Parse(#[from] serde_json::Error),
+
+    #[error("Unsupported Tiled map: {0}")]
+    Unsupported(String),
+
+    #[error(transparent)]
+    Asset(#[from] AssetError),
+
+    #[error("Failed to create script \"{name}\": {message}")]
+    Script { name: String, message: String },
+}
+
+/// The object type, or class, that marks where the camera starts.
+const SPAWN: &str = "spawn";
+/// The custom property that names the script attached to an object.
+const SCRIPT: &str = "script";
+/// Tile IDs store flip flags in their highest bits.
+const GID_MASK: u32 = 0x0FFF_FFFF;
+
+/// A map made with the [Tiled](https://www.mapeditor.org) editor, saved as JSON (`.tmj`).
+///
+/// Tile layers are merged into a single [`TileGrid`], where every tile is a wall showing its
+/// tile's image. Object layers become entities:
+///
+/// - Objects of type `spawn` set where the camera starts and which way it looks.
+/// - Tile objects become sprites showing their tile, as wide as the object.
+/// - Other objects become empties.
+///
+/// An object can carry a script: its `script` custom property names it in the
+/// [`ScriptRegistry`], and its other custom properties are the script's parameters.
+///
+/// Tilesets must be embedded in the map, and tile layers saved as CSV. Image paths are relative to
+/// the map, as Tiled writes them.
+#[derive(Debug)]
+pub struct TiledMap {
+    document: Document,
+    /// The directory the map was loaded from, which image paths are relative to.
+    directory: String,
+}
+
+impl TiledMap {
+    /// Parses a map whose image paths are relative to `directory` in the asset filesystem.
+    pub fn parse(source: &str, directory: &str) -> Result<Self, TiledError> {
+        let document: Document = serde_json::from_str(source)?;
+        if document.infinite {
+            return Err(TiledError::Unsupported("infinite maps".into()));
+        }
+        if document.orientation != "orthogonal" {
+            return Err(TiledError::Unsupported(format!(
+                "{} orientation",
+                document.orientation
+            )));
+        }
+        if document.tilewidth <= 0.0 || document.tileheight <= 0.0 {
+            return Err(TiledError::Unsupported(format!(
+                "tile size of {}x{}",
+                document.tilewidth, document.tileheight
+            )));
+        }
+        if let Some(tileset) = document.tilesets.iter().find(|t| t.source.is_some()) {
+            return Err(TiledError::Unsupported(format!(
+                "external tileset \"{}\", embed it in the map",
+                tileset.source.as_deref().unwrap_or_default()
+            )));
+        }
+        let encoded = document.layers().into_iter().any(|layer| match layer {
+            Layer::Tiles { data, encoding, .. } => {
+                encoding
+                    .as_deref()
+                    .is_some_and(|encoding| encoding != "csv")
+                    || !(data.is_null()
+                        || data.as_array().is_some_and(|gids| gids.iter().all(is_gid)))
+            }
+            _ => false,
+        });
+        if encoded {
+            return Err(TiledError::Unsupported(
+                "encoded tile layers, save them as CSV".into(),
+            ));
+        }
+
+        Ok(Self {
+            document,
+            directory: directory.into(),
+        })
+    }
+
+    #[inline]
+    pub fn width(&self) -> usize {
+        self.document.width
+    }
+
+    #[inline]
+    pub fn height(&self) -> usize {
+        self.document.height
+    }
+
+    /// Builds a scene from the map, loading images through `assets` and creating attached scripts
+    /// through `scripts`. Each tile is `tile_size` world units wide, as in [`GridBuilder`].
+    pub fn build(
+        &self,
+        assets: &Assets,
+        scripts: &ScriptRegistry,
+        tile_size: f32,
+    ) -> Result<Scene, TiledError> {
+        let mut textures = HashMap::new();
+        let mut grid = TileGrid::new(self.width(), self.height());
+        let mut builder = GridBuilder::new();
+        builder.tile_size(tile_size);
+
+        // Tiled uses 0 for empty tiles, and any other ID can be a wall
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                grid.set(x, y, 0u32);
+            }
+        }
+        for layer in self.document.layers() {
+            let Layer::Tiles {
+                data,
+                visible: true,
+                ..
+            } = layer
+            else {
+                continue;
+            };
+
+            // Parsing checked that every layer is an array of IDs
+            let gids = data.as_array().into_iter().flatten();
+            let gids = gids
+                .filter_map(|gid| gid.as_u64())
+                .map(|gid| gid as u32 & GID_MASK);
+            for (index, gid) in gids.enumerate() {
+                if gid == 0 || index >= self.width() * self.height() {
+                    continue;
+                }
+                grid.set(index % self.width(), index / self.width(), gid);
+                builder.tile(gid, self.texture(assets, gid, &mut textures)?);
+            }
+        }
+        let mut scene = builder.build(&grid);
+
+        // Tiled positions are in pixels, with y pointing down
+        let (sx, sy) = (
+            tile_size / self.document.tilewidth,
+            tile_size / self.document.tileheight,
+        );
+        for layer in self.document.layers() {
+            let Layer::Objects {
+                objects,
+                visible: true,
+            } = layer
+            else {
+                continue;
+            };
+
+            for object in objects {
+                // Rotations are clockwise on screen, so counterclockwise in the world
+                let dir = Vector::from_deg(-object.rotation);
+                let center = object.center();
+                let pos = Vector(center.x() * sx, -center.y() * sy);
+
+                if object.kind == SPAWN {
+                    scene.camera.set_pos(pos);
+                    scene.camera.set_dir(dir);
+                    continue;
+                }
+
+                let mut entity = match object.gid {
+                    Some(gid) => {
+                        let texture = self.texture(assets, gid & GID_MASK, &mut textures)?;
+                        let width = object.width * sx;
+                        let dir = Vector::RIGHT * width;
+                        let mut entity = Entity::from(Plane::new(pos - dir / 2.0, dir, texture));
+                        entity.add_script(Box::new(Billboard::new(width)));
+                        entity
+                    }
+                    None => Entity::from(Empty { pos, dir }),
+                };
+
+                if let Some((name, params)) = object.script() {
+                    let script =
+                        scripts
+                            .create(&name, &params)
+                            .map_err(|message| TiledError::Script {
+                                name: name.clone(),
+                                message,
+                            })?;
+                    entity.add_script(script);
+                }
+                scene.add(entity);
+            }
+        }
+
+        Ok(scene)
+    }
+
+    /// Loads the texture of a tile, either its own image or its part of the tileset's image.
+    fn texture(
+        &self,
+        assets: &Assets,
+        gid: u32,
+        textures: &mut HashMap<u32, Texture>,
+    ) -> Result<Texture, TiledError> {
+        if let Some(texture) = textures.get(&gid) {
+            return Ok(texture.cheap_clone());
+        }
+
+        let tileset = self
+            .document
+            .tilesets
+            .iter()
+            .filter(|tileset| tileset.firstgid <= gid)
+            .max_by_key(|tileset| tileset.firstgid)
+            .ok_or_else(|| TiledError::Unsupported(format!("tile {} has no tileset", gid)))?;
+        let id = gid - tileset.firstgid;
+        let path = |image: &str| format!("{}/{}", self.directory, image);
+
+        let own_image = tileset.tiles.iter().find(|tile| tile.id == id);
+        let texture = match (
+            own_image.and_then(|tile| tile.image.as_deref()),
+            &tileset.image,
+        ) {
+            (Some(image), _) => assets.load::<Texture>(&path(image))?.cheap_clone(),
+            (None, Some(image)) => {
+                let sheet = assets.load::<Image>(&path(image))?;
+                Texture::new(tileset.crop(&sheet, id))
+            }
+            (None, None) => {
+                return Err(TiledError::Unsupported(format!(
+                    "tile {} has no image",
+                    gid
+                )));
+            }
+        };
+
+        textures.insert(gid, texture.cheap_clone());
+        Ok(texture)
+    }
+}
+
+impl Asset for TiledMap {
+    fn load(assets: &Assets, path: &str) -> AssetResult<Self> {
+        let bytes = assets.vfs().read(path)?;
+        let directory = normalize_path(path)
+            .rsplit_once('/')
+            .map(|(directory, _)| directory.to_string())
+            .unwrap_or_default();
+
+        String::from_utf8(bytes)
+            .map_err(|e| e.to_string())
+            .and_then(|source| TiledMap::parse(&source, &directory).map_err(|e| e.to_string()))
+            .map_err(|message| AssetError::Decode {
+                path: path.into(),
+                message,
+            })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Document {
+    width: usize,
+    height: usize,
+    tilewidth: f32,
+    tileheight: f32,
+    #[serde(default = "orthogonal")]
+    orientation: String,
+    #[serde(default)]
+    infinite: bool,
+    #[serde(default)]
+    layers: Vec<Layer>,
+    #[serde(default)]
+    tilesets: Vec<Tileset>,
+}
+
+fn is_gid(value: &serde_json::Value) -> bool {
+    value.as_u64().is_some_and(|gid| gid <= u32::MAX as u64)
+}
+
+fn orthogonal() -> String {
+    "orthogonal".into()
+}
+
+fn visible() -> bool {
+    true
+}
+
+impl Document {
+    /// Every layer, with the layers of groups in place of the groups.
+    fn layers(&self) -> Vec<&Layer> {
+        fn flatten<'a>(layers: &'a [Layer], out: &mut Vec<&'a Layer>) {
+            for layer in layers {
+                match layer {
+                    Layer::Group {
+                        layers,
+                        visible: true,
+                    } => flatten(layers, out),
+                    Layer::Group { .. } => {}
+                    _ => out.push(layer),
+                }
+            }
+        }
+
+        let mut layers = Vec::new();
+        flatten(&self.layers, &mut layers);
+        layers
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+enum Layer {
+    #[serde(rename = "tilelayer")]
+    Tiles {
+        /// An array of tile IDs in CSV layers, or a string in encoded ones.
+        #[serde(default)]
+        data: serde_json::Value,
+        encoding: Option<String>,
+        #[serde(default = "visible")]
+        visible: bool,
+    },
+    #[serde(rename = "objectgroup")]
+    Objects {
+        #[serde(default)]
+        objects: Vec<Object>,
+        #[serde(default = "visible")]
+        visible: bool,
+    },
+    #[serde(rename = "group")]
+    Group {
+        #[serde(default)]
+        layers: Vec<Layer>,
+        #[serde(default = "visible")]
+        visible: bool,
+    },
+    #[serde(rename = "imagelayer")]
+    Image,
+}
+
+#[derive(Debug, Deserialize)]
+struct Object {
+    /// Tiled 1.9 calls the type a class.
+    #[serde(default, rename = "type", alias = "class")]
+    kind: String,
+    x: f32,
+    y: f32,
+    #[serde(default)]
+    width: f32,
+    #[serde(default)]
+    height: f32,
+    #[serde(default)]
+    rotation: f32,
+    gid: Option<u32>,
+    #[serde(default)]
+    properties: Vec<Property>,
+}
+
+impl Object {
+    /// The center of the object in pixels. Tile objects are anchored at their bottom left
+    /// corner, and other objects at their top left.
+    fn center(&self) -> Vector {
+        let height = match self.gid {
+            Some(_) => -self.height,
+            None => self.height,
+        };
+        Vector(self.x + self.width / 2.0, self.y + height / 2.0)
+    }
+
+    /// The script named by the `script` property, with the other properties as parameters.
+    fn script(&self) -> Option<(String, Params)> {
+        let name = self
+            .properties
+            .iter()
+            .find(|property| property.name == SCRIPT)?
+            .value
+            .as_str()?
+            .to_string();
+
+        let params = self
+            .properties
+            .iter()
+            .filter(|property| property.name != SCRIPT)
+            .filter_map(|property| {
+                let param = match &property.value {
+                    serde_json::Value::Bool(value) => Param::Bool(*value),
+                    serde_json::Value::Number(value) => Param::Number(value.as_f64()?),
+                    serde_json::Value::String(value) => Param::Text(value.clone()),
+                    _ => return None,
+                };
+                Some((property.name.clone(), param))
+            })
+            .collect();
+        Some((name, params))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Property {
+    name: String,
+    value: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct Tileset {
+    firstgid: u32,
+    /// Set for external tilesets, which aren't supported.
+    source: Option<String>,
+    /// The image of a tileset cut into tiles.
+    image: Option<String>,
+    #[serde(default)]
+    tilewidth: u32,
+    #[serde(default)]
+    tileheight: u32,
+    #[serde(default)]
+    columns: u32,
+    #[serde(default)]
+    margin: u32,
+    #[serde(default)]
+    spacing: u32,
+    /// The tiles of a collection of images, each with its own image.
+    #[serde(default)]
+    tiles: Vec<Tile>,
+}
+
+impl Tileset {
+    /// Copies a tile out of the tileset's image.
+    fn crop(&self, sheet: &Image, id: u32) -> Image {
+        let columns = self.columns.max(1);
+        let left = self.margin + (id % columns) * (self.tilewidth + self.spacing);
+        let top = self.margin + (id / columns) * (self.tileheight + self.spacing);
+
+        let tile = Image::new(self.tilewidth.max(1), self.tileheight.max(1));
+        for (x, y) in tile.coordinates() {
+            let (sx, sy) = (left + x, top + y);
+            if sx < sheet.width() && sy < sheet.height() {
+                tile.set(x, y, sheet.get(sx, sy));
+            }
+        }
+        tile
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Tile {
+    id: u32,
+    image: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Color,
+        assets::testing::{Idle, TestDir},
+    };
+
+    const SOURCE: &str = r#"{
+        "width": 4, "height": 3, "tilewidth": 32, "tileheight": 32,
+        "orientation": "orthogonal", "infinite": false,
+        "tilesets": [
+            {"firstgid": 1, "image": "sheet.png", "tilewidth": 2, "tileheight": 2, "columns": 2},
+            {"firstgid": 5, "tiles": [{"id": 0, "image": "images/barrel.png"}]}
+        ],
+        "layers": [
+            {"type": "tilelayer", "width": 4, "height": 3, "data": [
+                1, 1, 2, 1,
+                1, 0, 0, 1,
+                1, 1, 1, 1
+            ]},
+            {"type": "objectgroup", "objects": [
+                {"id": 1, "type": "spawn", "x": 48, "y": 48, "rotation": 90, "point": true},
+                {"id": 2, "gid": 5, "x": 64, "y": 64, "width": 16, "height": 32},
+                {"id": 3, "x": 40, "y": 40, "properties": [
+                    {"name": "script", "type": "string", "value": "spin"},
+                    {"name": "speed", "type": "float", "value": 9}
+                ]}
+            ]}
+        ]
+    }"#;
+
+    #[test]
+    fn builds_a_scene() {
+        let dir = TestDir::new("tiled");
+        let sheet = Image::new(4, 4);
+        sheet.set(2, 0, Color::RED);
+        dir.save_image("maps/sheet.png", &sheet);
+        dir.save_image("maps/images/barrel.png", &Image::new(2, 2));
+        std::fs::write(dir.join("maps/level.tmj"), SOURCE).unwrap();
+        let assets = dir.assets();
+        let mut scripts = ScriptRegistry::new();
+        scripts.register("spin", |params| match params["speed"].as_f32() {
+            Some(9.0) => Ok(Box::new(Idle)),
+            _ => Err("expected a speed of 9".into()),
+        });
+
+        let map = assets.load::<TiledMap>("maps/level.tmj").unwrap();
+        let mut scene = map.build(&assets, &scripts, 100.0).unwrap();
+
+        // The room's four sides, with the top split by tile 2, and the sprite
+        assert_eq!(scene.planes().count(), 6);
+        assert_eq!(scene.entities_mut().count(), 7);
+        assert_eq!(scene.camera.pos().x(), 150.0);
+        assert_eq!(scene.camera.pos().y(), -150.0);
+        assert!(scene.camera.dir().y() < -0.99);
+
+        // Tile 2 is the second tile of the sheet, with the red pixel
+        let top = scene
+            .planes()
+            .find(|plane| plane.texture.source().get(0, 0).r() == 255);
+        assert!(top.is_some());
+
+        assert!(matches!(
+            TiledMap::parse(
+                &SOURCE.replace("\"image\": \"sheet.png\"", "\"source\": \"a.tsx\""),
+                ""
+            ),
+            Err(TiledError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_unsupported_maps() {
+        let encoded =
+            r#""layers": [{"type": "tilelayer", "encoding": "base64", "data": "AQAAAA=="},"#;
+        let zero = r#""tilewidth": 0, "tileheight": 32"#;
+        for source in [
+            SOURCE.replace(r#""layers": ["#, encoded),
+            SOURCE.replace(r#""tilewidth": 32, "tileheight": 32"#, zero),
+        ] {
+            assert!(matches!(
+                TiledMap::parse(&source, ""),
+                Err(TiledError::Unsupported(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,405 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    ffi::OsStr,
+    fs::{self, File},
+    io::{self, BufReader, Cursor, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+use flate2::read::DeflateDecoder;
+use thiserror::Error;
+use zip::{CompressionMethod, ZipArchive, result::ZipError};
+
+#[derive(Error, Debug)]
+pub enum VfsError {
+    #[error("Asset not found: \"{0}\"")]
+    NotFound(String),
+
+    #[error("Failed to read assets: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Invalid pack: {0}")]
+    Zip(#[from] ZipError),
+}
+
+pub type VfsResult<T> = Result<T, VfsError>;
+
+/// A virtual filesystem that merges pk5 packs and loose directories into a single tree.
+///
+/// Paths are case-insensitive and always use `/` as separator. When several mounts contain the same
+/// path, the one with the highest priority wins; among mounts with the same priority, the one
+/// mounted last wins. Packs are indexed when mounted, but files are only read when requested.
+pub struct Vfs {
+    mounts: Vec<Mount>,
+    mounted: u32,
+}
+
+struct Mount {
+    priority: i32,
+    order: u32,
+    source: Source,
+}
+
+enum Source {
+    Dir(PathBuf),
+    Pack(Pack),
+}
+
+struct Pack {
+    path: PathBuf,
+    archive: Mutex<ZipArchive<BufReader<File>>>,
+    /// The archive index of every file, by normalized path.
+    entries: HashMap<String, usize>,
+}
+
+impl Vfs {
+    pub fn new() -> Self {
+        Self {
+            mounts: Vec::new(),
+            mounted: 0,
+        }
+    }
+
+    /// Mounts a directory of loose files.
+    pub fn mount_dir(&mut self, path: impl Into<PathBuf>, priority: i32) -> VfsResult<()> {
+        let path = path.into();
+        if !fs::metadata(&path)?.is_dir() {
+            return Err(VfsError::NotFound(path.display().to_string()));
+        }
+        self.mount(Source::Dir(path), priority);
+        Ok(())
+    }
+
+    /// Mounts a pk5 (zip) pack.
+    pub fn mount_pack(&mut self, path: impl Into<PathBuf>, priority: i32) -> VfsResult<()> {
+        let path = path.into();
+        let archive = ZipArchive::new(BufReader::new(File::open(&path)?))?;
+        let entries = archive
+            .file_names()
+            .filter(|name| !name.ends_with('/'))
+            .filter_map(|name| Some((normalize_path(name), archive.index_for_name(name)?)))
+            .collect();
+
+        self.mount(
+            Source::Pack(Pack {
+                path,
+                archive: Mutex::new(archive),
+                entries,
+            }),
+            priority,
+        );
+        Ok(())
+    }
+
+    /// Mounts every `.pk5` pack in a directory, in alphabetical order, so packs later in the
+    /// alphabet override earlier ones. Returns how many packs were mounted.
+    pub fn mount_packs_in(&mut self, dir: impl AsRef<Path>, priority: i32) -> VfsResult<usize> {
+        let mut packs = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let extension = path.extension().and_then(OsStr::to_str);
+            if path.is_file() && extension.is_some_and(|ext| ext.eq_ignore_ascii_case("pk5")) {
+                packs.push(path);
+            }
+        }
+        packs.sort();
+
+        for pack in &packs {
+            self.mount_pack(pack, priority)?;
+        }
+        Ok(packs.len())
+    }
+
+    fn mount(&mut self, source: Source, priority: i32) {
+        self.mounts.push(Mount {
+            priority,
+            order: self.mounted,
+            source,
+        });
+        self.mounted += 1;
+        self.mounts.sort_by_key(|mount| {
+            (
+                std::cmp::Reverse(mount.priority),
+                std::cmp::Reverse(mount.order),
+            )
+        });
+    }
+
+    /// When a file was last modified, if it is a loose file. Files in packs are never modified
+    /// while mounted.
+    pub fn modified(&self, path: &str) -> Option<SystemTime> {
+        match self.find(&normalize_path(path))? {
+            Location::Loose(path) => fs::metadata(path).and_then(|m| m.modified()).ok(),
+            Location::Packed(..) => None,
+        }
+    }
+
+    pub fn exists(&self, path: &str) -> bool {
+        self.find(&normalize_path(path)).is_some()
+    }
+
+    /// Reads a whole file.
+    pub fn read(&self, path: &str) -> VfsResult<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.open(path)?.read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Opens a file for streaming. Stored and deflated pack entries are decompressed as they are
+    /// read, without loading the whole file in memory.
+    pub fn open(&self, path: &str) -> VfsResult<Box<dyn Read + Send>> {
+        let normalized = normalize_path(path);
+        match self.find(&normalized) {
+            Some(Location::Loose(path)) => Ok(Box::new(BufReader::new(File::open(path)?))),
+            Some(Location::Packed(pack, index)) => pack.open(index),
+            None => Err(VfsError::NotFound(path.into())),
+        }
+    }
+
+    /// Every file in the filesystem, sorted.
+    pub fn files(&self) -> Vec<String> {
+        let mut files = BTreeSet::new();
+        for mount in &self.mounts {
+            match &mount.source {
+                Source::Dir(root) => walk(root, "", &mut files),
+                Source::Pack(pack) => files.extend(pack.entries.keys().cloned()),
+            }
+        }
+        files.into_iter().collect()
+    }
+
+    /// The files directly inside a directory, sorted.
+    pub fn list(&self, dir: &str) -> Vec<String> {
+        let dir = normalize_path(dir);
+        let prefix = if dir.is_empty() { dir } else { dir + "/" };
+        self.files()
+            .into_iter()
+            .filter(|file| {
+                file.strip_prefix(&prefix)
+                    .is_some_and(|name| !name.contains('/'))
+            })
+            .collect()
+    }
+
+    /// The files matching a pattern, sorted. `*` matches any part of a name, `?` any single
+    /// character and `**` any number of directories, e.g. `textures/**/*.png`.
+    pub fn glob(&self, pattern: &str) -> Vec<String> {
+        let pattern = normalize_path(pattern);
+        let pattern: Vec<_> = pattern.split('/').collect();
+        self.files()
+            .into_iter()
+            .filter(|file| glob_segments(&pattern, &file.split('/').collect::<Vec<_>>()))
+            .collect()
+    }
+
+    fn find(&self, normalized: &str) -> Option<Location<'_>> {
+        self.mounts.iter().find_map(|mount| match &mount.source {
+            Source::Dir(root) => resolve(root, normalized).map(Location::Loose),
+            Source::Pack(pack) => pack
+                .entries
+                .get(normalized)
+                .map(|index| Location::Packed(pack, *index)),
+        })
+    }
+}
+
+impl Default for Vfs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+enum Location<'a> {
+    Loose(PathBuf),
+    Packed(&'a Pack, usize),
+}
+
+impl Pack {
+    fn open(&self, index: usize) -> VfsResult<Box<dyn Read + Send>> {
+        let mut archive = self.archive.lock().unwrap();
+        let entry = archive.by_index_raw(index)?;
+        let (compression, data_start, size) = (
+            entry.compression(),
+            entry.data_start(),
+            entry.compressed_size(),
+        );
+        drop(entry);
+
+        // Stream the common compression methods straight from the pack through a separate file
+        // handle, so several files can be read at once
+        let stream = |file: &Path| -> VfsResult<_> {
+            let mut file = BufReader::new(File::open(file)?);
+            file.seek(SeekFrom::Start(data_start))?;
+            Ok(file.take(size))
+        };
+        match compression {
+            CompressionMethod::Stored => Ok(Box::new(stream(&self.path)?)),
+            CompressionMethod::Deflated => Ok(Box::new(DeflateDecoder::new(stream(&self.path)?))),
+            _ => {
+                let mut bytes = Vec::new();
+                archive.by_index(index)?.read_to_end(&mut bytes)?;
+                Ok(Box::new(Cursor::new(bytes)))
+            }
+        }
+    }
+}
+
+/// Normalizes a path for lookups: lowercase, `/` separated, without leading separators or `.`
+/// components, and with `..` components applied.
+pub fn normalize_path(path: &str) -> String {
+    let path = path.replace('\\', "/").to_lowercase();
+    let mut components: Vec<&str> = Vec::new();
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            _ => components.push(component),
+        }
+    }
+    components.join("/")
+}
+
+/// Finds a normalized path inside a directory, matching each component case-insensitively.
+fn resolve(root: &Path, normalized: &str) -> Option<PathBuf> {
+    let mut path = root.to_path_buf();
+    for component in normalized.split('/') {
+        let exact = path.join(component);
+        path = if exact.exists() {
+            exact
+        } else {
+            fs::read_dir(&path)
+                .ok()?
+                .filter_map(Result::ok)
+                .find(|entry| entry.file_name().to_string_lossy().to_lowercase() == component)?
+                .path()
+        };
+    }
+    path.is_file().then_some(path)
+}
+
+fn walk(dir: &Path, prefix: &str, files: &mut BTreeSet<String>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.filter_map(Result::ok) {
+        let name = format!(
+            "{}{}",
+            prefix,
+            entry.file_name().to_string_lossy().to_lowercase()
+        );
+        let path = entry.path();
+        if path.is_dir() {
+            walk(&path, &format!("{}/", name), files);
+        } else {
+            files.insert(name);
+        }
+    }
+}
+
+fn glob_segments(pattern: &[&str], path: &[&str]) -> bool {
+    match (pattern.first(), path.first()) {
+        (None, None) => true,
+        (Some(&"**"), _) => {
+            glob_segments(&pattern[1..], path)
+                || (!path.is_empty() && glob_segments(pattern, &path[1..]))
+        }
+        (Some(segment), Some(name)) => {
+            glob_name(segment.as_bytes(), name.as_bytes())
+                && glob_segments(&pattern[1..], &path[1..])
+        }
+        _ => false,
+    }
+}
+
+fn glob_name(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some(b'*'), _) => {
+            glob_name(&pattern[1..], name) || (!name.is_empty() && glob_name(pattern, &name[1..]))
+        }
+        (Some(b'?'), Some(_)) => glob_name(&pattern[1..], &name[1..]),
+        (Some(a), Some(b)) => a == b && glob_name(&pattern[1..], &name[1..]),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use zip::{ZipWriter, write::SimpleFileOptions};
+
+    use super::*;
+    use crate::assets::testing::TestDir;
+
+    fn write_pack(path: &Path, files: &[(&str, &[u8], CompressionMethod)]) {
+        let mut zip = ZipWriter::new(File::create(path).unwrap());
+        for (name, data, compression) in files {
+            let options = SimpleFileOptions::default().compression_method(*compression);
+            zip.start_file(*name, options).unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn normalizes_paths() {
+        assert_eq!(normalize_path("/Textures\\Wall.PNG"), "textures/wall.png");
+        assert_eq!(
+            normalize_path("./maps/../sounds//door.wav"),
+            "sounds/door.wav"
+        );
+    }
+
+    #[test]
+    fn globs() {
+        let matches = |pattern: &str, path: &str| {
+            let pattern: Vec<_> = pattern.split('/').collect();
+            glob_segments(&pattern, &path.split('/').collect::<Vec<_>>())
+        };
+        assert!(matches("textures/*.png", "textures/wall.png"));
+        assert!(!matches("textures/*.png", "textures/walls/brick.png"));
+        assert!(matches("textures/**/*.png", "textures/walls/brick.png"));
+        assert!(matches("**/door?.wav", "sounds/door1.wav"));
+    }
+
+    #[test]
+    fn mounts_by_priority() {
+        let dir = TestDir::new("vfs_priority");
+        write_pack(
+            &dir.join("base.pk5"),
+            &[
+                (
+                    "Textures/Wall.txt",
+                    b"pack wall",
+                    CompressionMethod::Deflated,
+                ),
+                (
+                    "textures/floor.txt",
+                    b"pack floor",
+                    CompressionMethod::Stored,
+                ),
+            ],
+        );
+        fs::write(dir.join("loose/textures/wall.txt"), b"loose wall").unwrap();
+
+        let mut vfs = Vfs::new();
+        vfs.mount_packs_in(dir.path(), 0).unwrap();
+        vfs.mount_dir(dir.join("loose"), 1).unwrap();
+
+        assert_eq!(vfs.read("TEXTURES/WALL.TXT").unwrap(), b"loose wall");
+        assert_eq!(vfs.read("textures/floor.txt").unwrap(), b"pack floor");
+        assert!(matches!(
+            vfs.read("missing.txt"),
+            Err(VfsError::NotFound(_))
+        ));
+        assert_eq!(
+            vfs.list("textures"),
+            vec!["textures/floor.txt", "textures/wall.txt"]
+        );
+    }
+}
@@ -0,0 +1,674 @@
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use crate::{Background, Color, Image, Plane, Scene, Sky, Texture, Vector, assets::*};
+
+#[derive(Error, Debug)]
+pub enum WadError {
+    #[error("Invalid WAD: {0}")]
+    Invalid(String),
+
+    #[error("Missing lump \"{0}\"")]
+    MissingLump(String),
+
+    #[error("Missing texture \"{0}\"")]
+    MissingTexture(String),
+}
+
+pub type WadResult<T> = Result<T, WadError>;
+
+/// Steps higher than this can't be climbed, so the line between them becomes a wall.
+const MAX_STEP: i32 = 24;
+/// Openings lower than this can't be walked through, so the line under them becomes a wall.
+const PLAYER_HEIGHT: i32 = 56;
+/// The lumps that make up a map, after its marker lump such as `E1M1` or `MAP01`.
+const MAP_LUMPS: [&str; 10] = [
+    "THINGS", "LINEDEFS", "SIDEDEFS", "VERTEXES", "SEGS", "SSECTORS", "NODES", "SECTORS", "REJECT",
+    "BLOCKMAP",
+];
+
+struct Lump {
+    name: String,
+    offset: usize,
+    size: usize,
+}
+
+/// A Doom WAD file: a directory of named lumps holding maps, pictures and palettes.
+///
+/// Lump names are case insensitive. When several lumps share a name, as when a PWAD replaces an
+/// IWAD's lumps, the last one wins.
+pub struct Wad {
+    data: Vec<u8>,
+    lumps: Vec<Lump>,
+}
+
+impl Wad {
+    pub fn parse(data: Vec<u8>) -> WadResult<Self> {
+        let magic = bytes(&data, 0, 4)?;
+        if magic != b"IWAD" && magic != b"PWAD" {
+            return Err(WadError::Invalid("not an IWAD or a PWAD".into()));
+        }
+
+        let count = i32_at(&data, 4)?.max(0) as usize;
+        let directory = i32_at(&data, 8)?.max(0) as usize;
+        // Check the directory fits before trusting its size
+        let size = count
+            .checked_mul(16)
+            .ok_or_else(|| WadError::Invalid("lump directory is too large".into()))?;
+        bytes(&data, directory, size)?;
+
+        let mut lumps = Vec::with_capacity(count);
+        for index in 0..count {
+            let entry = bytes(&data, directory + index * 16, 16)?;
+            let lump = Lump {
+                offset: i32_at(entry, 0)?.max(0) as usize,
+                size: i32_at(entry, 4)?.max(0) as usize,
+                name: name_at(entry, 8)?,
+            };
+            bytes(&data, lump.offset, lump.size)?;
+            lumps.push(lump);
+        }
+
+        Ok(Self { data, lumps })
+    }
+
+    pub fn lump_names(&self) -> impl Iterator<Item = &str> {
+        self.lumps.iter().map(|lump| lump.name.as_str())
+    }
+
+    pub fn lump(&self, name: &str) -> Option<&[u8]> {
+        self.lump_index(name).map(|index| self.lump_at(index))
+    }
+
+    fn lump_index(&self, name: &str) -> Option<usize> {
+        let name = name.to_ascii_uppercase();
+        self.lumps.iter().rposition(|lump| lump.name == name)
+    }
+
+    fn lump_at(&self, index: usize) -> &[u8] {
+        let lump = &self.lumps[index];
+        &self.data[lump.offset..lump.offset + lump.size]
+    }
+
+    fn require(&self, name: &str) -> WadResult<&[u8]> {
+        self.lump(name)
+            .ok_or_else(|| WadError::MissingLump(name.into()))
+    }
+
+    /// The names of the maps in the file, such as `E1M1` or `MAP01`.
+    pub fn maps(&self) -> Vec<&str> {
+        self.lumps
+            .windows(2)
+            .filter(|pair| pair[1].name == "THINGS")
+            .map(|pair| pair[0].name.as_str())
+            .collect()
+    }
+
+    /// Reads the geometry and things of a map.
+    pub fn map(&self, name: &str) -> WadResult<WadMap> {
+        let marker = self
+            .lump_index(name)
+            .ok_or_else(|| WadError::MissingLump(name.into()))?;
+        let lump = |lump: &str| {
+            self.lumps[marker + 1..]
+                .iter()
+                .take_while(|entry| MAP_LUMPS.contains(&entry.name.as_str()))
+                .position(|entry| entry.name == lump)
+                .map(|position| self.lump_at(marker + 1 + position))
+                .ok_or_else(|| WadError::MissingLump(format!("{}/{}", name, lump)))
+        };
+
+        let map = WadMap {
+            things: records(lump("THINGS")?, 10, |data| {
+                Ok(Thing {
+                    x: i16_at(data, 0)?,
+                    y: i16_at(data, 2)?,
+                    angle: i16_at(data, 4)?,
+                    kind: u16_at(data, 6)?,
+                    flags: u16_at(data, 8)?,
+                })
+            })?,
+            vertexes: records(lump("VERTEXES")?, 4, |data| {
+                Ok((i16_at(data, 0)?, i16_at(data, 2)?))
+            })?,
+            linedefs: records(lump("LINEDEFS")?, 14, |data| {
+                let side =
+                    |offset| u16_at(data, offset).map(|side| (side != u16::MAX).then_some(side));
+                Ok(Linedef {
+                    start: u16_at(data, 0)?,
+                    end: u16_at(data, 2)?,
+                    flags: u16_at(data, 4)?,
+                    special: u16_at(data, 6)?,
+                    tag: u16_at(data, 8)?,
+                    front: side(10)?,
+                    back: side(12)?,
+                })
+            })?,
+            sidedefs: records(lump("SIDEDEFS")?, 30, |data| {
+                Ok(Sidedef {
+                    x_offset: i16_at(data, 0)?,
+                    y_offset: i16_at(data, 2)?,
+                    upper: name_at(data, 4)?,
+                    lower: name_at(data, 12)?,
+                    middle: name_at(data, 20)?,
+                    sector: u16_at(data, 28)?,
+                })
+            })?,
+            sectors: records(lump("SECTORS")?, 26, |data| {
+                Ok(Sector {
+                    floor: i16_at(data, 0)?,
+                    ceiling: i16_at(data, 2)?,
+                    floor_texture: name_at(data, 4)?,
+                    ceiling_texture: name_at(data, 12)?,
+                    light: i16_at(data, 20)?,
+                    special: u16_at(data, 22)?,
+                    tag: u16_at(data, 24)?,
+                })
+            })?,
+        };
+        map.validate()?;
+        Ok(map)
+    }
+
+    /// The first palette of `PLAYPAL`, which pictures index into.
+    pub fn palette(&self) -> WadResult<Vec<Color>> {
+        let data = bytes(self.require("PLAYPAL")?, 0, 768)?;
+        Ok(data
+            .chunks_exact(3)
+            .map(|rgb| Color::rgb(rgb[0], rgb[1], rgb[2]))
+            .collect())
+    }
+
+    /// Decodes a picture lump, such as a wall patch or a sprite. Transparent pixels are black.
+    pub fn picture(&self, name: &str, palette: &[Color]) -> WadResult<Image> {
+        let data = self.require(name)?;
+        let width = u16_at(data, 0)? as u32;
+        let height = u16_at(data, 2)? as u32;
+        if width == 0 || height == 0 {
+            return Err(WadError::Invalid(format!("picture \"{}\" is empty", name)));
+        }
+
+        let image = Image::new(width, height);
+        draw_picture(data, palette, &image, 0, 0)?;
+        Ok(image)
+    }
+
+    /// Composes a wall texture from its patches, as listed in `TEXTURE1`, `TEXTURE2` and
+    /// `PNAMES`. Textures that aren't listed are looked up as plain pictures.
+    pub fn texture(&self, name: &str, palette: &[Color]) -> WadResult<Image> {
+        self.texture_from(&self.texture_defs()?, name, palette)
+    }
+
+    fn texture_from(
+        &self,
+        defs: &HashMap<String, TextureDef>,
+        name: &str,
+        palette: &[Color],
+    ) -> WadResult<Image> {
+        let name = name.to_ascii_uppercase();
+        let Some(def) = defs.get(&name) else {
+            return match self.lump_index(&name) {
+                Some(_) => self.picture(&name, palette),
+                None => Err(WadError::MissingTexture(name)),
+            };
+        };
+
+        let image = Image::new(def.width.max(1), def.height.max(1));
+        for (patch, x, y) in &def.patches {
+            let data = self.require(patch)?;
+            draw_picture(data, palette, &image, *x as i32, *y as i32)?;
+        }
+        Ok(image)
+    }
+
+    fn texture_defs(&self) -> WadResult<HashMap<String, TextureDef>> {
+        let mut defs = HashMap::new();
+        let Some(pnames) = self.lump("PNAMES") else {
+            return Ok(defs);
+        };
+        let patches = (0..i32_at(pnames, 0)?.max(0) as usize)
+            .map(|index| name_at(pnames, 4 + index * 8))
+            .collect::<WadResult<Vec<_>>>()?;
+
+        for lump in ["TEXTURE1", "TEXTURE2"] {
+            let Some(data) = self.lump(lump) else {
+                continue;
+            };
+            for index in 0..i32_at(data, 0)?.max(0) as usize {
+                let offset = i32_at(data, 4 + index * 4)?.max(0) as usize;
+                let header = bytes(data, offset, 22)?;
+                let count = i16_at(header, 20)?.max(0) as usize;
+
+                let mut def = TextureDef {
+                    width: i16_at(header, 12)?.max(0) as u32,
+                    height: i16_at(header, 14)?.max(0) as u32,
+                    patches: Vec::with_capacity(count),
+                };
+                for patch in 0..count {
+                    let entry = bytes(data, offset + 22 + patch * 10, 10)?;
+                    let index = i16_at(entry, 4)?.max(0) as usize;
+                    let name = patches.get(index).ok_or_else(|| {
+                        WadError::Invalid(format!("{} refers to a missing patch", lump))
+                    })?;
+                    def.patches
+                        .push((name.clone(), i16_at(entry, 0)?, i16_at(entry, 2)?));
+                }
+                defs.insert(name_at(header, 0)?, def);
+            }
+        }
+        Ok(defs)
+    }
+
+    /// Converts a map into a scene, at one world unit per map unit.
+    ///
+    /// The engine draws every wall at the same height, so sector heights are approximated. One
+    /// sided lines become walls with their middle texture. Two sided lines become walls with their
+    /// lower or upper texture only where the step between their sectors is too high to climb or
+    /// the opening too low to walk through, and otherwise only show their middle texture, if any.
+    /// The camera starts at the player 1 start, and a sky ceiling becomes the background.
+    pub fn scene(&self, map: &str) -> WadResult<Scene> {
+        let map = self.map(map)?;
+        let palette = self.palette()?;
+        let defs = self.texture_defs()?;
+        let mut textures: HashMap<String, Texture> = HashMap::new();
+
+        let mut scene = Scene::new();
+        for line in &map.linedefs {
+            let Some((side, name)) = map.wall(line) else {
+                continue;
+            };
+
+            if !textures.contains_key(name) {
+                let mut texture = Texture::new(self.texture_from(&defs, name, &palette)?);
+                texture.set_texel_density(Some(1.0));
+                textures.insert(name.into(), texture);
+            }
+            let mut texture = textures[name].cheap_clone();
+            texture.set_hoffset(side.x_offset as f32 / texture.source().width() as f32);
+            texture.set_voffset(side.y_offset as f32 / texture.source().height() as f32);
+
+            let start = map.vertex(line.start);
+            scene.add(Plane::new(start, map.vertex(line.end) - start, texture));
+        }
+
+        if let Some(start) = map.things.iter().find(|thing| thing.kind == 1) {
+            scene.camera.set_pos(Vector(start.x as f32, start.y as f32));
+            scene.camera.set_dir(Vector::from_deg(start.angle as f32));
+        }
+
+        let sky = map
+            .sectors
+            .iter()
+            .any(|sector| sector.ceiling_texture == "F_SKY1");
+        if sky && let Ok(image) = self.texture_from(&defs, "SKY1", &palette) {
+            scene.background = Background::Sky(Sky::new(image));
+        }
+
+        Ok(scene)
+    }
+}
+
+impl Asset for Wad {
+    fn load(assets: &Assets, path: &str) -> AssetResult<Self> {
+        let bytes = assets.vfs().read(path)?;
+        Wad::parse(bytes).map_err(|e| AssetError::Decode {
+            path: path.into(),
+            message: e.to_string(),
+        })
+    }
+}
+
+/// A composite wall texture: its size and patches with their origins.
+struct TextureDef {
+    width: u32,
+    height: u32,
+    patches: Vec<(String, i16, i16)>,
+}
+
+/// The geometry and things of a map, as stored in the WAD.
+#[derive(Clone, Debug, Default)]
+pub struct WadMap {
+    pub things: Vec<Thing>,
+    pub vertexes: Vec<(i16, i16)>,
+    pub linedefs: Vec<Linedef>,
+    pub sidedefs: Vec<Sidedef>,
+    pub sectors: Vec<Sector>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Thing {
+    pub x: i16,
+    pub y: i16,
+    /// In degrees, counterclockwise from east.
+    pub angle: i16,
+    /// The type of thing, such as `1` for the player 1 start.
+    pub kind: u16,
+    pub flags: u16,
+}
+
+#[derive(Clone, Debug)]
+pub struct Linedef {
+    pub start: u16,
+    pub end: u16,
+    pub flags: u16,
+    pub special: u16,
+    pub tag: u16,
+    /// The sidedef on the right of the line, looking from start to end.
+    pub front: Option<u16>,
+    pub back: Option<u16>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Sidedef {
+    pub x_offset: i16,
+    pub y_offset: i16,
+    /// Texture names, or `-` for none.
+    pub upper: String,
+    pub lower: String,
+    pub middle: String,
+    pub sector: u16,
+}
+
+#[derive(Clone, Debug)]
+pub struct Sector {
+    pub floor: i16,
+    pub ceiling: i16,
+    pub floor_texture: String,
+    pub ceiling_texture: String,
+    pub light: i16,
+    pub special: u16,
+    pub tag: u16,
+}
+
+impl WadMap {
+    fn validate(&self) -> WadResult<()> {
+        let invalid = |what: &str| Err(WadError::Invalid(format!("{} out of range", what)));
+        for line in &self.linedefs {
+            if line.start.max(line.end) as usize >= self.vertexes.len() {
+                return invalid("linedef vertex");
+            }
+            let mut sides = line.front.into_iter().chain(line.back);
+            if sides.any(|side| side as usize >= self.sidedefs.len()) {
+                return invalid("linedef sidedef");
+            }
+        }
+        if self
+            .sidedefs
+            .iter()
+            .any(|side| side.sector as usize >= self.sectors.len())
+        {
+            return invalid("sidedef sector");
+        }
+        Ok(())
+    }
+
+    fn vertex(&self, index: u16) -> Vector {
+        let (x, y) = self.vertexes[index as usize];
+        Vector(x as f32, y as f32)
+    }
+
+    /// The side and texture a line is drawn with, if it is drawn at all.
+    fn wall(&self, line: &Linedef) -> Option<(&Sidedef, &str)> {
+        let front = &self.sidedefs[line.front? as usize];
+        let Some(back) = line.back.map(|back| &self.sidedefs[back as usize]) else {
+            return textured(front, &[&front.middle]);
+        };
+
+        let front_sector = &self.sectors[front.sector as usize];
+        let back_sector = &self.sectors[back.sector as usize];
+        // Heights span the whole i16 range, so widen them before subtracting
+        let (front_floor, back_floor) = (front_sector.floor as i32, back_sector.floor as i32);
+        let ceiling = front_sector.ceiling.min(back_sector.ceiling) as i32;
+        let step = (back_floor - front_floor).abs();
+        let opening = ceiling - front_floor.max(back_floor);
+
+        if step > MAX_STEP || opening < PLAYER_HEIGHT {
+            textured(front, &[&front.lower, &front.upper, &front.middle])
+                .or_else(|| textured(back, &[&back.lower, &back.upper, &back.middle]))
+        } else {
+            textured(front, &[&front.middle])
+        }
+    }
+}
+
+/// The first texture that is set, with its side.
+fn textured<'a>(side: &'a Sidedef, names: &[&'a String]) -> Option<(&'a Sidedef, &'a str)> {
+    let name = names.iter().find(|name| name.as_str() != "-")?;
+    Some((side, name.as_str()))
+}
+
+/// Draws the posts of a picture lump onto `target` with its top left corner at `(x, y)`.
+fn draw_picture(data: &[u8], palette: &[Color], target: &Image, x: i32, y: i32) -> WadResult<()> {
+    let width = u16_at(data, 0)? as usize;
+    for column in 0..width {
+        let tx = x + column as i32;
+        let mut offset = u32_at(data, 8 + column * 4)? as usize;
+        loop {
+            let top = bytes(data, offset, 1)?[0];
+            if top == 0xFF {
+                break;
+            }
+
+            let (top, length) = (top as i32, bytes(data, offset + 1, 1)?[0] as usize);
+            let pixels = bytes(data, offset + 3, length)?;
+            for (row, &index) in pixels.iter().enumerate() {
+                let ty = y + top + row as i32;
+                let inside = (0..target.width() as i32).contains(&tx)
+                    && (0..target.height() as i32).contains(&ty);
+                if inside {
+                    let color = palette.get(index as usize).copied().unwrap_or(Color::BLACK);
+                    target.set(tx as u32, ty as u32, color);
+                }
+            }
+            offset += length + 4;
+        }
+    }
+    Ok(())
+}
+
+fn records<T>(data: &[u8], size: usize, read: impl Fn(&[u8]) -> WadResult<T>) -> WadResult<Vec<T>> {
+    data.chunks_exact(size).map(read).collect()
+}
+
+fn bytes(data: &[u8], offset: usize, len: usize) -> WadResult<&[u8]> {
+    offset
+        .checked_add(len)
+        .and_then(|end| data.get(offset..end))
+        .ok_or_else(|| WadError::Invalid("unexpected end of data".into()))
+}
+
+fn u16_at(data: &[u8], offset: usize) -> WadResult<u16> {
+    Ok(u16::from_le_bytes(
+        bytes(data, offset, 2)?.try_into().unwrap(),
+    ))
+}
+
+fn i16_at(data: &[u8], offset: usize) -> WadResult<i16> {
+    Ok(u16_at(data, offset)? as i16)
+}
+
+fn u32_at(data: &[u8], offset: usize) -> WadResult<u32> {
+    Ok(u32::from_le_bytes(
+        bytes(data, offset, 4)?.try_into().unwrap(),
+    ))
+}
+
+fn i32_at(data: &[u8], offset: usize) -> WadResult<i32> {
+    Ok(u32_at(data, offset)? as i32)
+}
+
+/// Reads an 8 byte, NUL padded lump or texture name.
+fn name_at(data: &[u8], offset: usize) -> WadResult<String> {
+    let name = bytes(data, offset, 8)?;
+    let len = name.iter().position(|&c| c == 0).unwrap_or(8);
+    Ok(String::from_utf8_lossy(&name[..len]).to_ascii_uppercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(name: &str) -> Vec<u8> {
+        let mut bytes = name.as_bytes().to_vec();
+        bytes.resize(8, 0);
+        bytes
+    }
+
+    fn shorts(values: &[i16]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    fn wad(lumps: Vec<(&str, Vec<u8>)>) -> Vec<u8> {
+        let mut data = b"PWAD".to_vec();
+        let directory = 12 + lumps.iter().map(|(_, lump)| lump.len()).sum::<usize>();
+        data.extend((lumps.len() as i32).to_le_bytes());
+        data.extend((directory as i32).to_le_bytes());
+
+        let mut entries = Vec::new();
+        for (lump_name, lump) in &lumps {
+            entries.extend((data.len() as i32).to_le_bytes());
+            entries.extend((lump.len() as i32).to_le_bytes());
+            entries.extend(name(lump_name));
+            data.extend(lump);
+        }
+        data.extend(entries);
+        data
+    }
+
+    fn sidedef(middle: &str, lower: &str, sector: i16) -> Vec<u8> {
+        [
+            shorts(&[0, 0]),
+            name("-"),
+            name(lower),
+            name(middle),
+            shorts(&[sector]),
+        ]
+        .concat()
+    }
+
+    fn sector(floor: i16) -> Vec<u8> {
+        [
+            shorts(&[floor, 128]),
+            name("FLAT"),
+            name("F_SKY1"),
+            shorts(&[160, 0, 0]),
+        ]
+        .concat()
+    }
+
+    /// Two 128 unit square rooms side by side, joined by a two sided line.
+    fn rooms(step: i16) -> Vec<u8> {
+        // A 2x2 patch with one red pixel, and a texture made of it
+        let patch = [
+            shorts(&[2, 2, 0, 0]),
+            16u32.to_le_bytes().to_vec(),
+            17u32.to_le_bytes().to_vec(),
+            vec![0xFF],
+            vec![0, 1, 0, 1, 0, 0xFF],
+        ]
+        .concat();
+        let texture1 = [
+            1i32.to_le_bytes().to_vec(),
+            8i32.to_le_bytes().to_vec(),
+            name("WALL"),
+            shorts(&[0, 0, 4, 4, 0, 0, 1]),
+            shorts(&[1, 1, 0, 1, 0]),
+        ]
+        .concat();
+        let mut playpal = vec![0; 768];
+        playpal[3] = 255;
+
+        let vertexes = shorts(&[0, 0, 128, 0, 256, 0, 256, 128, 128, 128, 0, 128]);
+        let line = |start: i16, end: i16, front: i16, back: i16| {
+            shorts(&[start, end, 0, 0, 0, front, back])
+        };
+        let linedefs = [
+            line(0, 1, 0, -1),
+            line(1, 2, 1, -1),
+            line(2, 3, 2, -1),
+            line(3, 4, 3, -1),
+            line(4, 5, 4, -1),
+            line(5, 0, 5, -1),
+            line(1, 4, 6, 7),
+        ]
+        .concat();
+        let sidedefs = [
+            sidedef("WALL", "-", 0),
+            sidedef("WALL", "-", 1),
+            sidedef("WALL", "-", 1),
+            sidedef("WALL", "-", 1),
+            sidedef("WALL", "-", 0),
+            sidedef("WALL", "-", 0),
+            sidedef("-", "WALL", 0),
+            sidedef("-", "-", 1),
+        ]
+        .concat();
+        let things = shorts(&[64, 64, 90, 1, 7]);
+
+        wad(vec![
+            ("PLAYPAL", playpal),
+            (
+                "PNAMES",
+                [1i32.to_le_bytes().to_vec(), name("PATCH")].concat(),
+            ),
+            ("TEXTURE1", texture1),
+            ("PATCH", patch.clone()),
+            ("SKY1", patch),
+            ("E1M1", Vec::new()),
+            ("THINGS", things),
+            ("LINEDEFS", linedefs),
+            ("SIDEDEFS", sidedefs),
+            ("VERTEXES", vertexes),
+            ("SECTORS", [sector(0), sector(step)].concat()),
+        ])
+    }
+
+    #[test]
+    fn reads_maps_and_textures() {
+        let wad = Wad::parse(rooms(0)).unwrap();
+        assert_eq!(wad.maps(), vec!["E1M1"]);
+        let map = wad.map("e1m1").unwrap();
+        assert_eq!(map.linedefs.len(), 7);
+        assert_eq!(map.linedefs[0].back, None);
+        assert_eq!(map.sidedefs[6].lower, "WALL");
+
+        // The patch is drawn at (1, 1), with a red pixel at the top of its second column
+        let palette = wad.palette().unwrap();
+        let texture = wad.texture("wall", &palette).unwrap();
+        assert_eq!(texture.dimensions(), (4, 4));
+        assert_eq!(texture.get(2, 1).r(), 255);
+        assert_eq!(texture.get(1, 1).r(), 0);
+
+        assert!(matches!(
+            Wad::parse(b"WAD".to_vec()),
+            Err(WadError::Invalid(_))
+        ));
+        assert!(matches!(wad.map("E1M2"), Err(WadError::MissingLump(_))));
+
+        // A directory that claims more lumps than the file holds
+        let header = [
+            b"PWAD".to_vec(),
+            i32::MAX.to_le_bytes().to_vec(),
+            12i32.to_le_bytes().to_vec(),
+        ];
+        assert!(Wad::parse(header.concat()).is_err());
+    }
+
+    #[test]
+    fn approximates_sector_heights() {
+        // A low step can be climbed, so the rooms stay open
+        let scene = Wad::parse(rooms(16)).unwrap().scene("E1M1").unwrap();
+        assert_eq!(scene.planes().count(), 6);
+        assert_eq!(scene.camera.pos().x(), 64.0);
+
+        // A high step becomes a wall
+        let scene = Wad::parse(rooms(64)).unwrap().scene("E1M1").unwrap();
+        assert_eq!(scene.planes().count(), 7);
+        assert!(matches!(scene.background, Background::Sky(_)));
+
+        // Steps across the whole height range don't overflow
+        let scene = Wad::parse(rooms(i16::MIN)).unwrap().scene("E1M1").unwrap();
+        assert_eq!(scene.planes().count(), 7);
+    }
+}
@@ -197,6 +197,13 @@ impl From<(f32, f32)> for Vector {
     }
 }
 
+impl From<Vector> for (f32, f32) {
+    #[inline]
+    fn from(vector: Vector) -> Self {
+        (vector.0, vector.1)
+    }
+}
+
 const TO_RAD: f32 = std::f32::consts::PI / 180.0;
 const TO_DEG: f32 = 180.0 / std::f32::consts::PI;
 
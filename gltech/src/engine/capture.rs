@@ -0,0 +1,109 @@
+use std::{path::PathBuf, time::Duration};
+
+use crate::Image;
+
+/// Keeps track of pending screenshots and the frame sequence being captured, if any.
+pub(crate) struct Capture {
+    screenshots: Vec<PathBuf>,
+    sequence: Option<Sequence>,
+}
+
+struct Sequence {
+    dir: PathBuf,
+    step: Duration,
+    frame: u32,
+}
+
+impl Capture {
+    pub(crate) fn new() -> Self {
+        Self {
+            screenshots: Vec::new(),
+            sequence: None,
+        }
+    }
+
+    pub(crate) fn screenshot(&mut self, path: PathBuf) {
+        self.screenshots.push(path);
+    }
+
+    pub(crate) fn start_sequence(&mut self, dir: PathBuf, fps: u32) -> Result<(), String> {
+        if fps == 0 {
+            return Err("Capture frame rate must be greater than zero".into());
+        }
+
+        std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        self.sequence = Some(Sequence {
+            dir,
+            step: Duration::from_secs(1) / fps,
+            frame: 0,
+        });
+        Ok(())
+    }
+
+    pub(crate) fn stop_sequence(&mut self) {
+        self.sequence = None;
+    }
+
+    /// The timestep scripts should be updated with, if a sequence is being captured.
+    pub(crate) fn fixed_step(&self) -> Option<Duration> {
+        self.sequence.as_ref().map(|sequence| sequence.step)
+    }
+
+    /// Saves the rendered frame, without the profiler overlay, to every pending screenshot and to
+    /// the current sequence.
+    pub(crate) fn save(&mut self, image: &Image) {
+        for path in self.screenshots.drain(..) {
+            if let Err(e) = image.save(&path) {
+                eprintln!("Failed to save screenshot to {}: {}", path.display(), e);
+            }
+        }
+
+        if let Some(sequence) = &mut self.sequence {
+            let path = sequence
+                .dir
+                .join(format!("frame_{:06}.png", sequence.frame));
+            sequence.frame += 1;
+            if let Err(e) = image.save(&path) {
+                eprintln!("Failed to save frame to {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Color;
+
+    #[test]
+    fn sequences_write_numbered_frames() {
+        let dir = std::env::temp_dir().join(format!("gltech_capture_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let image = Image::new(2, 2);
+        image.set(1, 0, Color::RED);
+
+        let mut capture = Capture::new();
+        let rejected = capture.start_sequence(dir.clone(), 0);
+        capture.start_sequence(dir.clone(), 25).unwrap();
+        let step = capture.fixed_step();
+        capture.save(&image);
+        capture.save(&image);
+        capture.stop_sequence();
+        capture.save(&image);
+
+        let mut names: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        let saved = ::image::open(dir.join("frame_000001.png")).map(|saved| saved.into_rgb8());
+        // Clean up before asserting, so a failure doesn't leave the directory behind
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(rejected.is_err());
+        assert_eq!(step, Some(Duration::from_millis(40)));
+        assert_eq!(capture.fixed_step(), None);
+        assert_eq!(names, vec!["frame_000000.png", "frame_000001.png"]);
+        assert_eq!(saved.unwrap().get_pixel(1, 0).0, [255, 0, 0]);
+    }
+}
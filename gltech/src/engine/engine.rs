@@ -1,15 +1,39 @@
-use std::time::Instant;
-
-use super::renderer;
-use crate::{Image, Input, Scene, SysRequest, SystemContext};
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use super::{
+    capture::Capture,
+    pacing::{FrameTimes, Pacer},
+    post_process::PostProcess,
+    profiler::Profiler,
+    render_scale::{RenderScale, Upscale},
+    renderer,
+};
+use crate::{Assets, Effect, Image, Input, LoopStage, Scene, SysRequest, SystemContext};
 use sdl2::{pixels::PixelFormatEnum, render::TextureCreator};
 
+/// How often hot reloading checks for changed files.
+const HOT_RELOAD_INTERVAL: Duration = Duration::from_millis(500);
+
 pub struct GLTechContext {
+    assets: Assets,
     borderless: bool,
+    capture: Option<(PathBuf, u32)>,
+    dynamic_resolution: Option<Duration>,
+    effects: Vec<Effect>,
+    background_fps: Option<u32>,
     fullscreen: bool,
+    hot_reload: bool,
+    max_fps: Option<u32>,
+    profiler: bool,
+    profiler_overlay: bool,
+    render_scale: f32,
     resolution: Option<(u32, u32)>,
     sdl: sdl2::Sdl,
     title: String,
+    upscale: Upscale,
     video: sdl2::VideoSubsystem,
     vsync: bool,
 }
@@ -19,27 +43,104 @@ pub fn init() -> Result<GLTechContext, String> {
     let video = sdl.video()?;
 
     Ok(GLTechContext {
+        assets: Assets::default(),
         borderless: false,
+        capture: None,
+        dynamic_resolution: None,
+        effects: Vec::new(),
+        background_fps: None,
         fullscreen: false,
+        hot_reload: false,
+        max_fps: None,
+        profiler: false,
+        profiler_overlay: false,
+        render_scale: 1.0,
         resolution: None,
         sdl,
         title: "GLTech 3".into(),
+        upscale: Upscale::Linear,
         video,
         vsync: false,
     })
 }
 
 impl GLTechContext {
+    /// Sets the asset cache scripts load from through [`SystemContext::assets`]. Unused assets are
+    /// freed whenever the scene changes.
+    pub fn assets(&mut self, assets: Assets) -> &mut Self {
+        self.assets = assets;
+        self
+    }
+
+    /// Caps the frame rate while the window is out of focus, to save power when the game runs in
+    /// the background.
+    pub fn background_fps(&mut self, background_fps: Option<u32>) -> &mut Self {
+        self.background_fps = background_fps;
+        self
+    }
+
     pub fn borderless(&mut self, borderless: bool) -> &mut Self {
         self.borderless = borderless;
         self
     }
 
+    /// Captures every frame into `dir` as a numbered image sequence from the moment the engine
+    /// launches. See [`SystemContext::start_capture`].
+    pub fn capture(&mut self, dir: impl Into<PathBuf>, fps: u32) -> &mut Self {
+        self.capture = Some((dir.into(), fps));
+        self
+    }
+
+    /// Lowers the render resolution while frames take longer than `target_frame_time` to render,
+    /// and raises it back up to the [`render_scale`](Self::render_scale) when there is time to
+    /// spare.
+    pub fn dynamic_resolution(&mut self, target_frame_time: Option<Duration>) -> &mut Self {
+        self.dynamic_resolution = target_frame_time;
+        self
+    }
+
+    /// Sets the post-processing effects applied to every frame before it is presented. See
+    /// [`SystemContext::set_effects`] to change them while running.
+    pub fn effects(&mut self, effects: Vec<Effect>) -> &mut Self {
+        self.effects = effects;
+        self
+    }
+
     pub fn fullscreen(&mut self, fullscreen: bool) -> &mut Self {
         self.fullscreen = fullscreen;
         self
     }
 
+    /// Development mode: watches the loose directories mounted in the [`assets`](Self::assets)
+    /// filesystem and reloads images that change on disk, without restarting or losing the scene
+    /// state. Files are checked twice per second.
+    pub fn hot_reload(&mut self, enabled: bool) -> &mut Self {
+        self.hot_reload = enabled;
+        self
+    }
+
+    /// Caps the frame rate. Frames are paced by sleeping and then spinning for the last moment, so
+    /// the cap is precise without keeping a core busy.
+    pub fn max_fps(&mut self, max_fps: Option<u32>) -> &mut Self {
+        self.max_fps = max_fps;
+        self
+    }
+
+    /// Profiles the main loop from the moment the engine launches, optionally showing the
+    /// profiler overlay. See [`SystemContext::set_profiling`].
+    pub fn profiler(&mut self, enabled: bool, overlay: bool) -> &mut Self {
+        self.profiler = enabled;
+        self.profiler_overlay = overlay;
+        self
+    }
+
+    /// Renders the scene at a fraction of the window resolution and stretches it to fill the
+    /// window, e.g. `0.5` for a retro look or to render faster. Values above `1.0` are clamped.
+    pub fn render_scale(&mut self, scale: f32) -> &mut Self {
+        self.render_scale = scale;
+        self
+    }
+
     pub fn resolution(&mut self, width: u32, height: u32) -> &mut Self {
         self.resolution = Some((width, height));
         self
@@ -50,16 +151,30 @@ impl GLTechContext {
         self
     }
 
+    /// Sets how frames rendered below the window resolution are stretched to fill the window.
+    pub fn upscale(&mut self, upscale: Upscale) -> &mut Self {
+        self.upscale = upscale;
+        self
+    }
+
     pub fn vsync(&mut self, vsync: bool) -> &mut Self {
         self.vsync = vsync;
         self
     }
 
     pub fn launch(self, mut scene: Scene) -> Result<(), String> {
-        let start_time = Instant::now();
+        let mut capture = Capture::new();
+        if let Some((dir, fps)) = &self.capture {
+            capture.start_sequence(dir.clone(), *fps)?;
+        }
+        let mut post_process = PostProcess::new(self.effects.clone());
+        let mut render_scale = RenderScale::new(self.render_scale, self.dynamic_resolution);
+        let mut pacer = Pacer::new(self.max_fps, self.background_fps);
+        let mut frame_times = FrameTimes::new();
+        let mut profiler = Profiler::new(self.profiler, self.profiler_overlay);
 
         // Run start functions even before creating the window
-        let mut system_context = SystemContext::new();
+        let mut system_context = SystemContext::new(self.assets.clone());
         scene.start(&mut system_context);
         if system_context.exit {
             return Ok(());
@@ -68,23 +183,60 @@ impl GLTechContext {
         // Spawn the window and create the screen texture and gltech surface
         let mut canvas = self.spawn_window()?;
         let texture_creator = canvas.texture_creator();
-        let mut screen_texture = self.get_screen_texture(&texture_creator)?;
-        let (width, height) = self.get_resolution()?;
+        let window_size = self.get_resolution()?;
+        let (width, height) = render_scale.surface_size(window_size);
+        let mut screen_texture = self.get_screen_texture(&texture_creator, width, height)?;
         let mut gltech_surface = crate::Image::new(width, height);
 
         // Get an event pump and start the main loop
         let mut event_pump = self.sdl.event_pump()?;
 
         // Main loop
+        let mut frame = 0;
+        let mut time = Duration::ZERO;
         let mut frame_time = Instant::now();
         let mut input_handler = Input::new();
+        let mut last_reload = Instant::now();
         loop {
+            profiler.begin_frame(frame);
+            system_context.script_times = profiler.enabled.then(Vec::new);
+
             // Process any requests from the last frame, such as changing resolution or fullscreen
-            self.process_requests(&mut system_context);
+            self.process_requests(
+                &mut system_context,
+                &mut capture,
+                &mut post_process,
+                &mut render_scale,
+                &mut pacer,
+                &mut profiler,
+            );
+
+            // Reload assets changed on disk in development mode
+            if self.hot_reload && last_reload.elapsed() >= HOT_RELOAD_INTERVAL {
+                last_reload = Instant::now();
+                system_context.assets.reload_changed();
+            }
+            profiler.mark(LoopStage::Requests);
+
+            // Reallocate the surface if the render scale changed
+            let (width, height) = render_scale.surface_size(window_size);
+            if gltech_surface.dimensions() != (width, height) {
+                screen_texture = self.get_screen_texture(&texture_creator, width, height)?;
+                gltech_surface = crate::Image::new(width, height);
+            }
 
-            // Render the scene to the surface
-            let planes: Vec<&crate::Plane> = scene.planes().collect();
-            renderer::draw_planes(&scene.camera, planes, &mut gltech_surface);
+            // Render the scene to the surface and apply post-processing
+            let render_start = Instant::now();
+            renderer::draw_scene(&scene, frame, time, &gltech_surface, &profiler.render_stats);
+            profiler.mark(LoopStage::Render);
+            post_process.apply(&gltech_surface);
+            render_scale.record(render_start.elapsed());
+            profiler.mark(LoopStage::PostProcess);
+
+            // Save the frame if a screenshot or capture was requested, before the profiler overlay
+            // is drawn over it
+            capture.save(&gltech_surface);
+            profiler.draw_overlay(&gltech_surface);
 
             // Present the surface on the screen
             Self::present(
@@ -92,27 +244,47 @@ impl GLTechContext {
                 &mut screen_texture,
                 gltech_surface.cheap_clone(),
             )?;
+            profiler.mark(LoopStage::Present);
 
             // Update input and check for exit event (usually window close)
             input_handler.update(event_pump.poll_iter());
             if input_handler.exit {
                 break;
             }
+            profiler.mark(LoopStage::Input);
 
-            // Update the scene with input and time data
-            let delta_time = frame_time.elapsed();
+            // Wait for the next frame if the frame rate is capped
+            pacer.wait(input_handler.has_focus());
+            profiler.mark(LoopStage::Pacing);
+            let real_delta_time = frame_time.elapsed();
             frame_time = Instant::now();
-            scene.update(
-                input_handler.clone(),
-                &mut system_context,
-                start_time.elapsed(),
-                delta_time,
-            );
+            frame_times.record(real_delta_time);
+            system_context.frame_stats = frame_times.stats();
+
+            // Update the scene with input and time data. While capturing a frame sequence, the
+            // scene advances by a fixed timestep instead of the real frame time.
+            let delta_time = capture.fixed_step().unwrap_or(real_delta_time);
+            time += delta_time;
+            frame += 1;
+            scene.update(input_handler.clone(), &mut system_context, time, delta_time);
+            profiler.mark(LoopStage::Scripts);
+
+            let script_times = system_context.script_times.take().unwrap_or_default();
+            system_context.profile = profiler.end_frame(script_times);
 
             // Check if any script requested exit
             if system_context.exit {
                 break;
             }
+
+            // Switch scenes if a script requested it, ending the old scene's scripts and freeing
+            // the assets only it used
+            if let Some(next_scene) = system_context.next_scene.take() {
+                scene.end(&mut system_context, time);
+                scene = next_scene;
+                scene.start(&mut system_context);
+                system_context.assets.free_unused();
+            }
         }
 
         Ok(())
@@ -146,7 +318,15 @@ impl GLTechContext {
         }
     }
 
-    fn process_requests(&self, system_context: &mut SystemContext) {
+    fn process_requests(
+        &self,
+        system_context: &mut SystemContext,
+        capture: &mut Capture,
+        post_process: &mut PostProcess,
+        render_scale: &mut RenderScale,
+        pacer: &mut Pacer,
+        profiler: &mut Profiler,
+    ) {
         for request in system_context.take_requests() {
             match request {
                 SysRequest::SetResolution(_, _) => todo!(),
@@ -156,6 +336,29 @@ impl GLTechContext {
                 }
                 SysRequest::SetTitle(_) => todo!(),
                 SysRequest::SetVSync(_) => todo!(),
+                SysRequest::Screenshot(path) => capture.screenshot(path),
+                SysRequest::StartCapture(dir, fps) => {
+                    if let Err(e) = capture.start_sequence(dir, fps) {
+                        eprintln!("Failed to start frame capture: {}", e);
+                    }
+                }
+                SysRequest::StopCapture => capture.stop_sequence(),
+                SysRequest::SetEffects(effects) => post_process.set_effects(effects),
+                SysRequest::AddEffect(effect) => post_process.add_effect(effect),
+                SysRequest::SetPostProcessing(enabled) => post_process.set_enabled(enabled),
+                SysRequest::SetRenderScale(scale) => render_scale.set_scale(scale),
+                SysRequest::SetDynamicResolution(target) => render_scale.set_target(target),
+                SysRequest::SetMaxFps(max_fps) => pacer.max_fps = max_fps,
+                SysRequest::SetBackgroundFps(background_fps) => {
+                    pacer.background_fps = background_fps
+                }
+                SysRequest::SetProfiling(enabled) => profiler.enabled = enabled,
+                SysRequest::SetProfilerOverlay(visible) => profiler.overlay = visible,
+                SysRequest::ExportProfile(path) => {
+                    if let Err(e) = profiler.export_csv(&path) {
+                        eprintln!("Failed to export profile: {}", e);
+                    }
+                }
             }
         }
     }
@@ -190,14 +393,14 @@ impl GLTechContext {
     fn get_screen_texture<'r>(
         &self,
         texture_creator: &'r TextureCreator<sdl2::video::WindowContext>,
+        width: u32,
+        height: u32,
     ) -> Result<sdl2::render::Texture<'r>, String> {
-        let (width, height) = self.get_resolution()?;
-
         let mut texture = texture_creator
             .create_texture_static(PixelFormatEnum::ARGB8888, width, height)
             .map_err(|e| e.to_string())?;
 
-        texture.set_scale_mode(sdl2::render::ScaleMode::Best);
+        texture.set_scale_mode(self.upscale.scale_mode());
         Ok(texture)
     }
 }
@@ -1,4 +1,5 @@
 pub use sdl2::event::Event;
+use sdl2::event::WindowEvent;
 pub use sdl2::keyboard::Scancode;
 pub use sdl2::mouse::MouseButton;
 use std::rc::Rc;
@@ -12,6 +13,7 @@ pub struct Input {
     mouse_pos: (i32, i32),
     mouse_rel: (i32, i32),
     events: Rc<[Event]>,
+    focused: bool,
     pub(crate) exit: bool,
 }
 
@@ -25,6 +27,7 @@ impl Input {
             mouse_pos: (0, 0),
             mouse_rel: (0, 0),
             events: Rc::new([]),
+            focused: true,
             exit: false,
         }
     }
@@ -63,6 +66,14 @@ impl Input {
                 Event::MouseButtonUp { mouse_btn, .. } => {
                     self.mouse_down &= !(1 << (*mouse_btn as u8));
                 }
+                Event::Window {
+                    win_event: WindowEvent::FocusGained,
+                    ..
+                } => self.focused = true,
+                Event::Window {
+                    win_event: WindowEvent::FocusLost,
+                    ..
+                } => self.focused = false,
                 _ => {}
             })
             .collect();
@@ -95,6 +106,11 @@ impl Input {
         self.mouse_rel
     }
 
+    /// Whether the window has keyboard focus.
+    pub fn has_focus(&self) -> bool {
+        self.focused
+    }
+
     pub fn events(&self) -> impl Iterator<Item = &Event> {
         self.events.iter()
     }
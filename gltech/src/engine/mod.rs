@@ -1,6 +1,15 @@
+mod capture;
 mod engine;
 pub mod input;
+mod pacing;
+mod post_process;
+mod profiler;
+mod render_scale;
 mod renderer;
 
 pub use engine::*;
 pub use input::*;
+pub use pacing::FrameStats;
+pub use post_process::Effect;
+pub use profiler::{LoopStage, Profile};
+pub use render_scale::Upscale;
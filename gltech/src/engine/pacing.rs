@@ -0,0 +1,133 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// How long before the deadline the pacer stops sleeping and spins instead. Sleeps can overshoot
+/// by about a scheduler tick, so the last stretch is waited out precisely.
+const SPIN_MARGIN: Duration = Duration::from_millis(2);
+/// How many of the latest frames [`FrameStats`] are computed over.
+const STATS_WINDOW: usize = 240;
+
+/// Caps the frame rate by waiting out the rest of each frame.
+pub(crate) struct Pacer {
+    pub(crate) max_fps: Option<u32>,
+    pub(crate) background_fps: Option<u32>,
+    deadline: Instant,
+}
+
+impl Pacer {
+    pub(crate) fn new(max_fps: Option<u32>, background_fps: Option<u32>) -> Self {
+        Self {
+            max_fps,
+            background_fps,
+            deadline: Instant::now(),
+        }
+    }
+
+    /// Waits until the next frame is due. While the window is out of focus, the background frame
+    /// rate applies instead, if it is set.
+    pub(crate) fn wait(&mut self, focused: bool) {
+        let fps = match (focused, self.background_fps) {
+            (false, Some(background_fps)) => Some(background_fps),
+            _ => self.max_fps,
+        };
+        let Some(fps) = fps.filter(|fps| *fps > 0) else {
+            self.deadline = Instant::now();
+            return;
+        };
+
+        let interval = Duration::from_secs(1) / fps;
+        self.deadline += interval;
+
+        // Don't try to catch up on frames that ran long, which would cause a burst of short frames
+        let now = Instant::now();
+        if self.deadline < now {
+            self.deadline = now;
+            return;
+        }
+
+        if let Some(sleep) = (self.deadline - now).checked_sub(SPIN_MARGIN) {
+            std::thread::sleep(sleep);
+        }
+        while Instant::now() < self.deadline {
+            std::hint::spin_loop();
+        }
+    }
+}
+
+/// Frame time statistics over the last few seconds of frames.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FrameStats {
+    pub average: Duration,
+    /// The frame time 99% of frames stay under.
+    pub p99: Duration,
+    pub min: Duration,
+    pub max: Duration,
+}
+
+impl FrameStats {
+    /// The average frames per second.
+    pub fn fps(&self) -> f32 {
+        if self.average.is_zero() {
+            return 0.0;
+        }
+        1.0 / self.average.as_secs_f32()
+    }
+}
+
+/// The latest frame times, from which [`FrameStats`] are computed.
+pub(crate) struct FrameTimes {
+    times: VecDeque<Duration>,
+}
+
+impl FrameTimes {
+    pub(crate) fn new() -> Self {
+        Self {
+            times: VecDeque::with_capacity(STATS_WINDOW),
+        }
+    }
+
+    pub(crate) fn record(&mut self, frame_time: Duration) {
+        if self.times.len() == STATS_WINDOW {
+            self.times.pop_front();
+        }
+        self.times.push_back(frame_time);
+    }
+
+    pub(crate) fn stats(&self) -> FrameStats {
+        if self.times.is_empty() {
+            return FrameStats::default();
+        }
+
+        let mut sorted: Vec<_> = self.times.iter().copied().collect();
+        sorted.sort_unstable();
+        let p99 = ((sorted.len() * 99).div_ceil(100)).clamp(1, sorted.len()) - 1;
+
+        FrameStats {
+            average: sorted.iter().sum::<Duration>() / sorted.len() as u32,
+            p99: sorted[p99],
+            min: sorted[0],
+            max: sorted[sorted.len() - 1],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_over_frame_times() {
+        let mut frame_times = FrameTimes::new();
+        for millis in 1..=100 {
+            frame_times.record(Duration::from_millis(millis));
+        }
+
+        let stats = frame_times.stats();
+        assert_eq!(stats.min, Duration::from_millis(1));
+        assert_eq!(stats.max, Duration::from_millis(100));
+        assert_eq!(stats.p99, Duration::from_millis(99));
+        assert_eq!(stats.average, Duration::from_micros(50_500));
+    }
+}
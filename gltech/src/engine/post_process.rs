@@ -0,0 +1,303 @@
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::{Color, Image};
+
+/// The 4x4 Bayer matrix used by [`Effect::Dither`].
+const BAYER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// An effect applied to each rendered frame before it is presented. Effects run in the order they
+/// are given.
+#[derive(Clone, Debug)]
+pub enum Effect {
+    /// Gamma correction. Values above 1 brighten the midtones, values below 1 darken them.
+    Gamma(f32),
+    /// Adds `brightness`, from -1 to 1, then scales the distance to mid gray by `contrast`.
+    BrightnessContrast { brightness: f32, contrast: f32 },
+    /// Blends the frame towards a color by `amount`, from 0 to 1. Useful for damage flashes.
+    Tint { color: Color, amount: f32 },
+    /// Darkens the frame towards its corners. Darkening starts at `radius`, a fraction of the
+    /// distance from the center to a corner, and reaches `strength` at the corners.
+    Vignette { radius: f32, strength: f32 },
+    /// Darkens every other line by `intensity`, from 0 to 1.
+    Scanlines { intensity: f32 },
+    /// Reduces each channel to `levels` values, using ordered dithering to hide the banding.
+    Dither { levels: u8 },
+    /// Replaces every pixel with the closest color of a palette.
+    Palette(Vec<Color>),
+    /// Shows the frame in square blocks of `size` pixels.
+    Pixelate { size: u32 },
+}
+
+/// An effect prepared for fast per pixel application.
+enum Stage {
+    /// Maps each channel value through a table.
+    Lut(Box<[u8; 256]>),
+    Tint {
+        color: Color,
+        amount: f32,
+    },
+    Vignette {
+        radius: f32,
+        strength: f32,
+    },
+    Scanlines {
+        factor: f32,
+    },
+    Dither {
+        step: f32,
+    },
+    /// The closest palette color for every color with 5 bits per channel.
+    Palette(Box<[Color]>),
+    Pixelate {
+        size: u32,
+    },
+}
+
+impl Stage {
+    fn new(effect: &Effect) -> Option<Self> {
+        let stage = match effect {
+            Effect::Gamma(gamma) => {
+                let exponent = 1.0 / gamma.max(f32::EPSILON);
+                Stage::Lut(lut(|value| value.powf(exponent)))
+            }
+            Effect::BrightnessContrast {
+                brightness,
+                contrast,
+            } => Stage::Lut(lut(|value| (value + brightness - 0.5) * contrast + 0.5)),
+            Effect::Tint { color, amount } => Stage::Tint {
+                color: *color,
+                amount: amount.clamp(0.0, 1.0),
+            },
+            Effect::Vignette { radius, strength } => Stage::Vignette {
+                radius: *radius,
+                strength: strength.clamp(0.0, 1.0),
+            },
+            Effect::Scanlines { intensity } => Stage::Scanlines {
+                factor: 1.0 - intensity.clamp(0.0, 1.0),
+            },
+            Effect::Dither { levels } => Stage::Dither {
+                step: 255.0 / ((*levels).max(2) - 1) as f32,
+            },
+            Effect::Palette(palette) if palette.is_empty() => return None,
+            Effect::Palette(palette) => Stage::Palette(
+                (0..1 << 15)
+                    .map(|index| {
+                        let expand = |bits: u32| ((bits & 31) << 3 | (bits & 31) >> 2) as u8;
+                        let color =
+                            Color::rgb(expand(index >> 10), expand(index >> 5), expand(index));
+                        closest(palette, color)
+                    })
+                    .collect(),
+            ),
+            Effect::Pixelate { size } if *size <= 1 => return None,
+            Effect::Pixelate { size } => Stage::Pixelate { size: *size },
+        };
+        Some(stage)
+    }
+
+    #[inline]
+    fn pixel(&self, color: Color, x: u32, y: u32, image: &Image) -> Color {
+        match self {
+            Stage::Lut(table) => Color::rgb(
+                table[color.r() as usize],
+                table[color.g() as usize],
+                table[color.b() as usize],
+            ),
+            Stage::Tint {
+                color: tint,
+                amount,
+            } => color.lerp(*tint, *amount),
+            Stage::Vignette { radius, strength } => {
+                let dx = (x as f32 + 0.5) / image.widthf * 2.0 - 1.0;
+                let dy = (y as f32 + 0.5) / image.heightf * 2.0 - 1.0;
+                let distance = (dx * dx + dy * dy).sqrt() * std::f32::consts::FRAC_1_SQRT_2;
+                let t = ((distance - radius) / (1.0 - radius).max(f32::EPSILON)).clamp(0.0, 1.0);
+                color.lerp(Color::BLACK, t * t * strength)
+            }
+            Stage::Scanlines { factor } if y % 2 == 1 => scale(color, *factor),
+            Stage::Scanlines { .. } => color,
+            Stage::Dither { step } => {
+                let threshold = (BAYER[(y & 3) as usize][(x & 3) as usize] as f32 + 0.5) / 16.0;
+                let quantize = |value: u8| {
+                    let level = (value as f32 / step + threshold - 0.5).round();
+                    (level * step).clamp(0.0, 255.0) as u8
+                };
+                Color::rgb(
+                    quantize(color.r()),
+                    quantize(color.g()),
+                    quantize(color.b()),
+                )
+            }
+            Stage::Palette(table) => {
+                let index = (color.r() as usize >> 3) << 10
+                    | (color.g() as usize >> 3) << 5
+                    | color.b() as usize >> 3;
+                table[index]
+            }
+            Stage::Pixelate { .. } => color,
+        }
+    }
+}
+
+/// The post-processing pipeline run by the engine between rendering and presenting.
+pub(crate) struct PostProcess {
+    effects: Vec<Effect>,
+    stages: Vec<Stage>,
+    enabled: bool,
+}
+
+impl PostProcess {
+    pub(crate) fn new(effects: Vec<Effect>) -> Self {
+        let mut post_process = Self {
+            effects: Vec::new(),
+            stages: Vec::new(),
+            enabled: true,
+        };
+        post_process.set_effects(effects);
+        post_process
+    }
+
+    pub(crate) fn set_effects(&mut self, effects: Vec<Effect>) {
+        self.stages = effects.iter().filter_map(Stage::new).collect();
+        self.effects = effects;
+    }
+
+    pub(crate) fn add_effect(&mut self, effect: Effect) {
+        let mut effects = std::mem::take(&mut self.effects);
+        effects.push(effect);
+        self.set_effects(effects);
+    }
+
+    pub(crate) fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub(crate) fn apply(&self, image: &Image) {
+        if !self.enabled {
+            return;
+        }
+
+        // Consecutive per pixel effects are fused into a single pass over the image
+        let mut start = 0;
+        for (index, stage) in self.stages.iter().enumerate() {
+            if let Stage::Pixelate { size } = stage {
+                pixel_pass(&self.stages[start..index], image);
+                pixelate(*size, image);
+                start = index + 1;
+            }
+        }
+        pixel_pass(&self.stages[start..], image);
+    }
+}
+
+fn pixel_pass(stages: &[Stage], image: &Image) {
+    if stages.is_empty() {
+        return;
+    }
+
+    (0..image.height()).into_par_iter().for_each(|y| {
+        for x in 0..image.width() {
+            let color = stages.iter().fold(image.get(x, y), |color, stage| {
+                stage.pixel(color, x, y, image)
+            });
+            image.set_unsafe(x, y, color);
+        }
+    });
+}
+
+/// Fills each block with the color at its center. Rows of blocks are processed in parallel.
+fn pixelate(size: u32, image: &Image) {
+    let (width, height) = image.dimensions();
+    (0..height.div_ceil(size))
+        .into_par_iter()
+        .for_each(|block_y| {
+            let y0 = block_y * size;
+            let y1 = (y0 + size).min(height);
+            for x0 in (0..width).step_by(size as usize) {
+                let x1 = (x0 + size).min(width);
+                let color = image.get((x0 + x1) / 2, (y0 + y1) / 2);
+                for y in y0..y1 {
+                    for x in x0..x1 {
+                        image.set_unsafe(x, y, color);
+                    }
+                }
+            }
+        });
+}
+
+/// Builds a channel lookup table from a function over values normalized to `[0, 1]`.
+fn lut(f: impl Fn(f32) -> f32) -> Box<[u8; 256]> {
+    let mut table = Box::new([0; 256]);
+    for (value, entry) in table.iter_mut().enumerate() {
+        *entry = (f(value as f32 / 255.0) * 255.0).round().clamp(0.0, 255.0) as u8;
+    }
+    table
+}
+
+#[inline]
+fn scale(color: Color, factor: f32) -> Color {
+    let channel = |value: u8| (value as f32 * factor) as u8;
+    Color::rgb(channel(color.r()), channel(color.g()), channel(color.b()))
+}
+
+fn closest(palette: &[Color], color: Color) -> Color {
+    let distance = |other: &&Color| {
+        let dr = color.r() as i32 - other.r() as i32;
+        let dg = color.g() as i32 - other.g() as i32;
+        let db = color.b() as i32 - other.b() as i32;
+        dr * dr + dg * dg + db * db
+    };
+    *palette.iter().min_by_key(distance).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filled(color: Color) -> Image {
+        let image = Image::new(4, 4);
+        for (x, y) in image.coordinates() {
+            image.set(x, y, color);
+        }
+        image
+    }
+
+    #[test]
+    fn neutral_gamma_keeps_colors() {
+        let image = filled(Color::rgb(10, 128, 250));
+        PostProcess::new(vec![Effect::Gamma(1.0)]).apply(&image);
+
+        let color = image.get(2, 3);
+        assert_eq!((color.r(), color.g(), color.b()), (10, 128, 250));
+    }
+
+    #[test]
+    fn palette_picks_closest_color() {
+        let image = filled(Color::rgb(200, 30, 20));
+        PostProcess::new(vec![Effect::Palette(vec![
+            Color::BLACK,
+            Color::RED,
+            Color::WHITE,
+        ])])
+        .apply(&image);
+
+        let color = image.get(0, 0);
+        assert_eq!((color.r(), color.g(), color.b()), (255, 0, 0));
+    }
+
+    #[test]
+    fn dither_uses_only_the_given_levels() {
+        let image = Image::new(8, 8);
+        for (x, y) in image.coordinates() {
+            image.set(x, y, Color::rgb((x * 32) as u8, (y * 32) as u8, 100));
+        }
+        PostProcess::new(vec![Effect::Dither { levels: 2 }]).apply(&image);
+
+        for (x, y) in image.coordinates() {
+            let color = image.get(x, y);
+            for channel in [color.r(), color.g(), color.b()] {
+                assert!(channel == 0 || channel == 255);
+            }
+        }
+    }
+}
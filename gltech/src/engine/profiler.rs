@@ -0,0 +1,298 @@
+use std::{
+    collections::VecDeque,
+    fmt::Write as _,
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use crate::{Color, Image};
+
+/// How many frames of history the profiler keeps for the overlay and CSV export.
+const HISTORY: usize = 600;
+
+/// A stage of the engine's main loop.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LoopStage {
+    /// Handling requests made by scripts through the system context.
+    Requests,
+    Render,
+    PostProcess,
+    /// Saving the frame if capturing, drawing the profiler overlay and uploading the frame to the
+    /// window.
+    Present,
+    Input,
+    /// Waiting for the next frame when the frame rate is capped.
+    Pacing,
+    Scripts,
+}
+
+impl LoopStage {
+    pub const ALL: [LoopStage; 7] = [
+        LoopStage::Requests,
+        LoopStage::Render,
+        LoopStage::PostProcess,
+        LoopStage::Present,
+        LoopStage::Input,
+        LoopStage::Pacing,
+        LoopStage::Scripts,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LoopStage::Requests => "requests",
+            LoopStage::Render => "render",
+            LoopStage::PostProcess => "post_process",
+            LoopStage::Present => "present",
+            LoopStage::Input => "input",
+            LoopStage::Pacing => "pacing",
+            LoopStage::Scripts => "scripts",
+        }
+    }
+
+    /// The color of the stage in the profiler overlay.
+    fn color(self) -> Color {
+        match self {
+            LoopStage::Requests => Color::MAGENTA,
+            LoopStage::Render => Color::GREEN,
+            LoopStage::PostProcess => Color::CYAN,
+            LoopStage::Present => Color::BLUE,
+            LoopStage::Input => Color::YELLOW,
+            LoopStage::Pacing => Color::GRAY,
+            LoopStage::Scripts => Color::RED,
+        }
+    }
+}
+
+/// Timings and renderer statistics for a single frame.
+#[derive(Clone, Debug, Default)]
+pub struct Profile {
+    pub frame: u64,
+    stages: [Duration; LoopStage::ALL.len()],
+    /// The time each script took to tick, in update order, named by [`Script::name`].
+    ///
+    /// [`Script::name`]: crate::Script::name
+    pub scripts: Vec<(&'static str, Duration)>,
+    /// Rays cast by the renderer, including rays continued through portals.
+    pub rays: u64,
+    /// Ray-plane intersection tests performed by the renderer.
+    pub planes_tested: u64,
+}
+
+impl Profile {
+    #[inline]
+    pub fn stage(&self, stage: LoopStage) -> Duration {
+        self.stages[stage as usize]
+    }
+
+    /// The time spent in every stage, which is the frame time.
+    pub fn total(&self) -> Duration {
+        self.stages.iter().sum()
+    }
+
+    pub fn planes_per_ray(&self) -> f32 {
+        if self.rays == 0 {
+            return 0.0;
+        }
+        self.planes_tested as f32 / self.rays as f32
+    }
+}
+
+/// Counters updated by the renderer's worker threads.
+#[derive(Default)]
+pub(crate) struct RenderStats {
+    rays: AtomicU64,
+    planes_tested: AtomicU64,
+}
+
+impl RenderStats {
+    #[inline]
+    pub(crate) fn add(&self, rays: u64, planes_tested: u64) {
+        self.rays.fetch_add(rays, Ordering::Relaxed);
+        self.planes_tested
+            .fetch_add(planes_tested, Ordering::Relaxed);
+    }
+
+    fn take(&self) -> (u64, u64) {
+        (
+            self.rays.swap(0, Ordering::Relaxed),
+            self.planes_tested.swap(0, Ordering::Relaxed),
+        )
+    }
+}
+
+/// Times the stages of the main loop while enabled and keeps a history of recent frames.
+pub(crate) struct Profiler {
+    pub(crate) enabled: bool,
+    pub(crate) overlay: bool,
+    pub(crate) render_stats: RenderStats,
+    current: Profile,
+    history: VecDeque<Profile>,
+    last_mark: Instant,
+}
+
+impl Profiler {
+    pub(crate) fn new(enabled: bool, overlay: bool) -> Self {
+        Self {
+            enabled,
+            overlay,
+            render_stats: RenderStats::default(),
+            current: Profile::default(),
+            history: VecDeque::with_capacity(HISTORY),
+            last_mark: Instant::now(),
+        }
+    }
+
+    pub(crate) fn begin_frame(&mut self, frame: u64) {
+        self.current = Profile {
+            frame,
+            ..Default::default()
+        };
+        self.render_stats.take();
+        self.last_mark = Instant::now();
+    }
+
+    /// Adds the time since the last mark to a stage.
+    pub(crate) fn mark(&mut self, stage: LoopStage) {
+        if !self.enabled {
+            return;
+        }
+        let now = Instant::now();
+        self.current.stages[stage as usize] += now - self.last_mark;
+        self.last_mark = now;
+    }
+
+    /// Finishes the frame and returns its profile, if profiling.
+    pub(crate) fn end_frame(&mut self, scripts: Vec<(&'static str, Duration)>) -> Option<Profile> {
+        if !self.enabled {
+            return None;
+        }
+
+        let (rays, planes_tested) = self.render_stats.take();
+        let mut profile = std::mem::take(&mut self.current);
+        profile.scripts = scripts;
+        profile.rays = rays;
+        profile.planes_tested = planes_tested;
+
+        if self.history.len() == HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(profile.clone());
+        Some(profile)
+    }
+
+    /// Draws a graph of recent frame times in the bottom left corner, one column per frame with
+    /// the stages stacked in different colors. Lines mark 60 and 30 frames per second.
+    pub(crate) fn draw_overlay(&self, image: &Image) {
+        if !self.enabled || !self.overlay {
+            return;
+        }
+
+        let (width, height) = image.dimensions();
+        let pixels_per_ms = (height as f32 / 100.0).max(1.0);
+        let columns = self.history.len().min(width as usize);
+        let frames = self.history.iter().skip(self.history.len() - columns);
+
+        for (x, profile) in frames.enumerate() {
+            let mut y = height as f32;
+            for stage in LoopStage::ALL {
+                let bar = profile.stage(stage).as_secs_f32() * 1000.0 * pixels_per_ms;
+                let top = (y - bar).max(0.0);
+                for line in top as u32..y as u32 {
+                    image.set_unsafe(x as u32, line, stage.color());
+                }
+                y = top;
+            }
+        }
+
+        for fps in [60.0, 30.0] {
+            let y = height as f32 - 1000.0 / fps * pixels_per_ms;
+            if y >= 0.0 {
+                for x in 0..columns as u32 {
+                    image.set_unsafe(x, y as u32, Color::WHITE);
+                }
+            }
+        }
+    }
+
+    /// Writes the recorded history as CSV, with times in microseconds. Each script type gets its
+    /// own column, summing every instance of it.
+    pub(crate) fn export_csv(&self, path: &Path) -> Result<(), String> {
+        let mut script_names: Vec<&'static str> = Vec::new();
+        for (name, _) in self.history.iter().flat_map(|profile| &profile.scripts) {
+            if !script_names.contains(name) {
+                script_names.push(name);
+            }
+        }
+
+        let mut csv = String::from("frame");
+        for stage in LoopStage::ALL {
+            write!(csv, ",{}_us", stage.name()).unwrap();
+        }
+        csv.push_str(",total_us,rays,planes_tested");
+        for name in &script_names {
+            write!(csv, ",\"script:{}\"", name).unwrap();
+        }
+        csv.push('\n');
+
+        for profile in &self.history {
+            write!(csv, "{}", profile.frame).unwrap();
+            for stage in LoopStage::ALL {
+                write!(csv, ",{}", profile.stage(stage).as_micros()).unwrap();
+            }
+            write!(
+                csv,
+                ",{},{},{}",
+                profile.total().as_micros(),
+                profile.rays,
+                profile.planes_tested
+            )
+            .unwrap();
+            for name in &script_names {
+                let time: Duration = profile
+                    .scripts
+                    .iter()
+                    .filter(|(script, _)| script == name)
+                    .map(|(_, time)| *time)
+                    .sum();
+                write!(csv, ",{}", time.as_micros()).unwrap();
+            }
+            csv.push('\n');
+        }
+
+        std::fs::write(path, csv).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_has_a_column_per_script() {
+        let mut profiler = Profiler::new(true, false);
+        for frame in 0..3 {
+            profiler.begin_frame(frame);
+            profiler.render_stats.add(10, 40);
+            profiler.mark(LoopStage::Render);
+            let profile = profiler
+                .end_frame(vec![
+                    ("Spin", Duration::from_micros(5)),
+                    ("Spin", Duration::from_micros(7)),
+                ])
+                .unwrap();
+            assert_eq!(profile.planes_per_ray(), 4.0);
+        }
+
+        let path = std::env::temp_dir().join("gltech_profiler_test.csv");
+        profiler.export_csv(&path).unwrap();
+        let csv = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].ends_with(",rays,planes_tested,\"script:Spin\""));
+        assert!(lines[3].starts_with("2,"));
+        assert!(lines[3].ends_with(",10,40,12"));
+    }
+}
@@ -0,0 +1,122 @@
+use std::time::Duration;
+
+/// The lowest scale dynamic resolution goes down to.
+const MIN_DYNAMIC_SCALE: f32 = 0.25;
+/// How many frames dynamic resolution averages before adjusting the scale.
+const ADJUST_INTERVAL: u32 = 30;
+
+/// How the rendered frame is stretched to fill the window when rendering below the window
+/// resolution.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Upscale {
+    /// Sharp, blocky pixels.
+    Nearest,
+    /// Smooth interpolation between pixels.
+    Linear,
+}
+
+impl Upscale {
+    pub(crate) fn scale_mode(self) -> sdl2::render::ScaleMode {
+        match self {
+            Upscale::Nearest => sdl2::render::ScaleMode::Nearest,
+            Upscale::Linear => sdl2::render::ScaleMode::Linear,
+        }
+    }
+}
+
+/// Decides the resolution the engine renders at, relative to the window.
+///
+/// With a target frame time set, the scale drops while rendering takes longer than the target and
+/// climbs back up to the configured scale while there is time to spare.
+pub(crate) struct RenderScale {
+    /// The scale set by the user, which dynamic resolution never exceeds.
+    max_scale: f32,
+    scale: f32,
+    target: Option<Duration>,
+    elapsed: Duration,
+    frames: u32,
+}
+
+impl RenderScale {
+    pub(crate) fn new(scale: f32, target: Option<Duration>) -> Self {
+        let scale = scale.clamp(f32::EPSILON, 1.0);
+        Self {
+            max_scale: scale,
+            scale,
+            target,
+            elapsed: Duration::ZERO,
+            frames: 0,
+        }
+    }
+
+    pub(crate) fn set_scale(&mut self, scale: f32) {
+        *self = Self::new(scale, self.target);
+    }
+
+    pub(crate) fn set_target(&mut self, target: Option<Duration>) {
+        *self = Self::new(self.max_scale, target);
+    }
+
+    /// The size of the surface to render for a window of the given size.
+    pub(crate) fn surface_size(&self, (width, height): (u32, u32)) -> (u32, u32) {
+        let scaled = |size: u32| ((size as f32 * self.scale).round() as u32).max(1);
+        (scaled(width), scaled(height))
+    }
+
+    /// Records how long a frame took to render, adjusting the scale if dynamic resolution is on.
+    pub(crate) fn record(&mut self, render_time: Duration) {
+        let Some(target) = self.target else {
+            return;
+        };
+
+        self.elapsed += render_time;
+        self.frames += 1;
+        if self.frames < ADJUST_INTERVAL {
+            return;
+        }
+
+        let average = self.elapsed.as_secs_f32() / self.frames as f32;
+        self.elapsed = Duration::ZERO;
+        self.frames = 0;
+
+        // Rendering cost grows with the pixel count, which is the square of the scale. Leave some
+        // headroom below the target so the scale doesn't bounce around it.
+        let ratio = target.as_secs_f32() / average.max(f32::EPSILON);
+        if (0.8..1.0).contains(&ratio) {
+            return;
+        }
+        let factor = ratio.sqrt().clamp(0.75, 1.1);
+        let min_scale = MIN_DYNAMIC_SCALE.min(self.max_scale);
+        self.scale = (self.scale * factor).clamp(min_scale, self.max_scale);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dynamic_scale_follows_target() {
+        let target = Duration::from_millis(10);
+        let mut render_scale = RenderScale::new(1.0, Some(target));
+
+        for _ in 0..ADJUST_INTERVAL * 4 {
+            render_scale.record(Duration::from_millis(40));
+        }
+        let (lowered, _) = render_scale.surface_size((1000, 1000));
+        assert!(lowered < 1000);
+
+        for _ in 0..ADJUST_INTERVAL * 20 {
+            render_scale.record(Duration::from_millis(1));
+        }
+        let (raised, _) = render_scale.surface_size((1000, 1000));
+        assert!(raised > lowered);
+        assert!(raised <= 1000);
+    }
+
+    #[test]
+    fn surface_size_never_empty() {
+        let render_scale = RenderScale::new(0.001, None);
+        assert_eq!(render_scale.surface_size((640, 480)), (1, 1));
+    }
+}
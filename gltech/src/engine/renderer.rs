@@ -1,74 +1,522 @@
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 
-use crate::{Camera, Plane, prelude::*};
-use std::f32;
+use crate::{
+    Background, Camera, Color, Feed, Plane, Projection, Scene, Sky, Viewport, WALL_HEIGHT,
+    prelude::*, trace,
+};
+use std::{f32, ops::Range, time::Duration};
+
+use super::profiler::RenderStats;
 
 use crate::Image;
 
-pub fn draw_planes(camera: &Camera, planes: Vec<&Plane>, image: &Image) {
-    let (width, height) = image.dimensions();
-    unsafe {
-        std::ptr::write_bytes(image.u32_buffer(), 0, (width * height) as usize);
-    }
-    let tan = (camera.fov * 0.5 * f32::consts::PI / 180.0).tan();
-    let step0 = 2.0 * tan / image.widthf;
-    let col_height_1 = 100.0 * image.widthf / (2.0 * tan);
-    let camera_pos = camera.pos();
-    let camera_dir = camera.dir();
-    let camera_left = Vector(-camera_dir.1, camera_dir.0);
-
-    (0..width).into_par_iter().for_each(|col| {
-        let (width, height) = image.dimensions();
-        let ray = {
-            let delta = (width >> 1) as i32 - col as i32;
-            let dir = camera_dir + camera_left * step0 * delta as f32;
-            Ray::new(camera_pos, dir)
-        };
+/// A rectangle of the output image, in pixels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
 
-        let Some((plane, (collision_r, collision_s))) = get_nearest(&planes, ray) else {
-            return;
-        };
+impl Rect {
+    pub fn full(image: &Image) -> Self {
+        Self {
+            x: 0,
+            y: 0,
+            width: image.width(),
+            height: image.height(),
+        }
+    }
 
-        let col_h = col_height_1 / (ray.dir.dot_product(camera_dir) * collision_r);
-        let col_start = (image.heightf - 1.0 - col_h) * 0.5 + col_h * (camera.z / 100.0 - 0.5);
-        let col_end = (image.heightf - 1.0 + col_h) * 0.5 + col_h * (camera.z / 100.0 - 0.5);
+    /// The largest rectangle with the given aspect ratio centered inside this one.
+    pub fn fit(self, aspect: f32) -> Self {
+        let current = self.width as f32 / self.height as f32;
+        if current > aspect {
+            let width = ((self.height as f32 * aspect) as u32).clamp(1, self.width);
+            Self {
+                x: self.x + (self.width - width) / 2,
+                width,
+                ..self
+            }
+        } else {
+            let height = ((self.width as f32 / aspect) as u32).clamp(1, self.height);
+            Self {
+                y: self.y + (self.height - height) / 2,
+                height,
+                ..self
+            }
+        }
+    }
+
+    /// Maps a viewport onto an image, rounding its edges to the nearest pixel.
+    pub fn from_viewport(viewport: Viewport, image: &Image) -> Self {
+        let x0 = (viewport.x * image.widthf).round().clamp(0.0, image.widthf) as u32;
+        let y0 = (viewport.y * image.heightf)
+            .round()
+            .clamp(0.0, image.heightf) as u32;
+        let x1 = ((viewport.x + viewport.width) * image.widthf)
+            .round()
+            .clamp(x0 as f32, image.widthf) as u32;
+        let y1 = ((viewport.y + viewport.height) * image.heightf)
+            .round()
+            .clamp(y0 as f32, image.heightf) as u32;
 
-        let mut draw_col_start = height as i32 - (image.heightf - col_start) as i32; // Inclusive
-        let mut draw_col_end = height as i32 - (image.heightf - col_end) as i32; // Exclusive
+        Self {
+            x: x0,
+            y: y0,
+            width: x1 - x0,
+            height: y1 - y0,
+        }
+    }
 
-        if draw_col_start < 0 {
-            draw_col_start = 0;
+    pub fn fill(&self, image: &Image, color: Color) {
+        for y in self.y..self.y + self.height {
+            for x in self.x..self.x + self.width {
+                image.set_unsafe(x, y, color);
+            }
         }
+    }
+}
+
+pub(crate) fn draw_scene(
+    scene: &Scene,
+    frame: u64,
+    time: Duration,
+    image: &Image,
+    stats: &RenderStats,
+) {
+    refresh_live_textures(scene, frame, time, stats);
+
+    // Anything not covered by the main camera stays black
+    let full = Rect::full(image);
+    if Rect::from_viewport(scene.camera.viewport, image) != full {
+        full.fill(image, Color::BLACK);
+    }
+
+    // Cameras are drawn in order, so the ones added later are drawn on top of the others. Each
+    // view is rendered in parallel by columns.
+    for camera in scene.cameras() {
+        let rect = Rect::from_viewport(camera.viewport, image);
+        if rect.width == 0 || rect.height == 0 {
+            continue;
+        }
+
+        let rect = match camera.aspect {
+            Some(aspect) => {
+                rect.fill(image, Color::BLACK);
+                rect.fit(aspect)
+            }
+            None => rect,
+        };
+
+        draw_view(scene, camera, rect, time, image, stats);
+    }
+}
 
-        if draw_col_end >= height as i32 {
-            draw_col_end = height as i32;
+/// Redraws the live textures that are due on this frame. Every live texture is drawn before any
+/// of them is shown, so they all see the same state of each other.
+fn refresh_live_textures(scene: &Scene, frame: u64, time: Duration, stats: &RenderStats) {
+    let due: Vec<_> = scene
+        .planes()
+        .filter_map(|plane| Some((plane, plane.texture.refresh_due(frame)?)))
+        .collect();
+
+    for &(plane, (feed, target)) in &due {
+        let rect = Rect::full(target);
+        match feed {
+            Feed::Camera(camera) => draw_view(scene, camera, rect, time, target, stats),
+            Feed::Mirror => {
+                let eye = &scene.camera;
+                let segment = plane.segment;
+                // Reflect the eye across the mirror's line and look through the mirror from there
+                let foot = segment.start
+                    + segment.dir
+                        * ((eye.pos() - segment.start).dot_product(segment.dir)
+                            / segment.dir.dot_product(segment.dir));
+                let mut reflection = Camera::default();
+                reflection.set_pos(foot * 2.0 - eye.pos());
+                reflection.z = eye.z;
+
+                let window = Window {
+                    start: segment.start,
+                    step: segment.dir / rect.width as f32,
+                    distance: (foot - eye.pos()).mag(),
+                };
+                let lens = Lens::through(window, rect);
+                draw(scene, &reflection, &lens, rect, time, target, stats);
+            }
         }
+    }
 
+    for (plane, _) in due {
+        plane.texture.present();
+    }
+}
+
+pub(crate) fn draw_view(
+    scene: &Scene,
+    camera: &Camera,
+    rect: Rect,
+    time: Duration,
+    image: &Image,
+    stats: &RenderStats,
+) {
+    let lens = Lens::new(camera, rect);
+    draw(scene, camera, &lens, rect, time, image, stats);
+}
+
+fn draw(
+    scene: &Scene,
+    camera: &Camera,
+    lens: &Lens,
+    rect: Rect,
+    time: Duration,
+    image: &Image,
+    stats: &RenderStats,
+) {
+    let planes: Vec<&Plane> = scene.planes().collect();
+    let filter = scene.filter;
+
+    // Pitch is applied by shearing: the whole view moves vertically so the horizon lies where the
+    // pitched view direction crosses the screen. A view through a window has the horizon where the
+    // eye's height crosses the window instead.
+    let center = (rect.height as f32 - 1.0) * 0.5;
+    let horizon = match lens.window {
+        Some(_) => rect.height as f32 * (1.0 - camera.z / WALL_HEIGHT),
+        None => center + lens.shear(camera.pitch()),
+    };
+    let background = BackgroundRows::new(&scene.background, lens, center, horizon, rect);
+
+    (0..rect.width).into_par_iter().for_each(|col| {
+        let height = rect.height;
+        let x = rect.x + col;
+        let ray = Ray::new(camera.pos(), lens.ray_dir(camera.pos(), col));
+        let (hit, transform, casts) = trace(&planes, ray, lens.near);
+        stats.add(casts as u64, (casts as usize * planes.len()) as u64);
+        let Some((plane, (collision_r, collision_s))) = hit else {
+            background.draw(image, rect, col, transform.ray(ray), 0..height);
+            return;
+        };
+        // Depth follows the original ray, so the view through a portal keeps its perspective
+        let depth = lens.depth(ray, collision_r);
+        let ray = transform.ray(ray);
+        let col_h = WALL_HEIGHT * lens.focal / depth;
+        let (col_start, col_end) = match lens.projection {
+            Projection::Fisheye => (
+                horizon - lens.focal * ((WALL_HEIGHT - camera.z) / depth).atan(),
+                horizon + lens.focal * (camera.z / depth).atan(),
+            ),
+            _ => (
+                horizon - col_h * 0.5 + col_h * (camera.z / WALL_HEIGHT - 0.5),
+                horizon + col_h * 0.5 + col_h * (camera.z / WALL_HEIGHT - 0.5),
+            ),
+        };
+
+        let heightf = height as f32;
+        let draw_col_start = height as i32 - (heightf - col_start) as i32; // Inclusive
+        let draw_col_end = height as i32 - (heightf - col_end) as i32; // Exclusive
+
+        let draw_col_start = draw_col_start.clamp(0, height as i32) as u32;
+        let draw_col_end = draw_col_end.clamp(draw_col_start as i32, height as i32) as u32;
+
+        background.draw(image, rect, col, ray, 0..draw_col_start);
+        background.draw(image, rect, col, ray, draw_col_end..height);
+
+        // Estimate the texel footprint of this column's pixels to filter the texture. Vertically, a
+        // pixel spans 1 / col_h of the texture; horizontally, it spans the distance to where the
+        // next column's ray hits the same plane.
         let i_col_h = 1.0 / col_h;
+        let next_ray = transform.ray(Ray::new(camera.pos(), lens.ray_dir(camera.pos(), col + 1)));
+        let (_, next_s) = next_ray.get_rs(plane.segment);
+        let (su, sv) = plane.texture.uv_scale(plane.segment.dir.mag(), WALL_HEIGHT);
+        let sampler =
+            plane
+                .texture
+                .sampler((next_s - collision_s) * su, i_col_h * sv, filter, time);
+
+        let u = collision_s * su;
         for line in draw_col_start..draw_col_end {
-            let v = (line as f32 - col_start) * i_col_h;
-            let color = plane.texture.map_nearest(collision_s, v);
-            image.set_unsafe(col as u32, line as u32, color);
+            let v = match lens.projection {
+                Projection::Fisheye => {
+                    let elevation = (horizon - line as f32) / lens.focal;
+                    1.0 - (camera.z + depth * elevation.tan()) / WALL_HEIGHT
+                }
+                _ => (line as f32 - col_start) * i_col_h,
+            };
+            let color = sampler.map(u, v * sv);
+            image.set_unsafe(x, rect.y + line, color);
         }
     });
 }
 
-fn get_nearest<'a>(planes: &Vec<&'a Plane>, ray: Ray) -> Option<(&'a Plane, (f32, f32))> {
-    let mut rs = (f32::INFINITY, f32::INFINITY);
-    let mut nearest_plane = None;
+/// A segment of the world a view is seen through, split evenly across the columns of the view.
+/// Mirrors are drawn this way, with the eye placed behind the window.
+#[derive(Clone, Copy)]
+struct Window {
+    start: Vector,
+    /// The distance along the window covered by one column.
+    step: Vector,
+    /// The distance from the eye to the window's line.
+    distance: f32,
+}
 
-    for plane in planes {
-        let (distance, split) = ray.get_rs(plane.segment);
+/// The camera's projection, prepared for the rectangle it renders to.
+struct Lens {
+    projection: Projection,
+    window: Option<Window>,
+    /// The ray parameter below which hits are ignored, so that nothing between the eye and the
+    /// window is drawn.
+    near: f32,
+    /// Pixels per world unit at distance 1 for rectilinear projection, or pixels per radian for
+    /// the angular projections.
+    focal: f32,
+    half_width: f32,
+    camera_dir: Vector,
+    camera_left: Vector,
+}
 
-        if distance < 0.0 || split < 0.0 || split >= 1.0 {
-            continue;
+impl Lens {
+    fn new(camera: &Camera, rect: Rect) -> Self {
+        let widthf = rect.width as f32;
+        let half_fov = (camera.fov * 0.5).to_radians();
+        let focal = match camera.projection {
+            Projection::Rectilinear => widthf / (2.0 * half_fov.tan()),
+            Projection::Cylindrical | Projection::Fisheye => widthf / (2.0 * half_fov),
         };
+        let camera_dir = camera.dir();
+
+        Self {
+            projection: camera.projection,
+            window: None,
+            near: 0.0,
+            focal,
+            half_width: (rect.width >> 1) as f32,
+            camera_dir,
+            camera_left: Vector(-camera_dir.1, camera_dir.0),
+        }
+    }
 
-        if distance < rs.0 {
-            rs = (distance, split);
-            nearest_plane = Some(plane);
+    /// A lens whose rays go from the eye through each column's point on a window. Rays are scaled
+    /// to reach the window at `r = 1`, which also makes the window the full height of the view.
+    fn through(window: Window, rect: Rect) -> Self {
+        Self {
+            projection: Projection::Rectilinear,
+            window: Some(window),
+            near: 1.0 + 1e-3,
+            focal: rect.height as f32 / WALL_HEIGHT,
+            half_width: (rect.width >> 1) as f32,
+            camera_dir: Vector::FORWARD,
+            camera_left: Vector::LEFT,
         }
     }
 
-    Some((nearest_plane?, rs))
+    /// The direction of the ray cast through a column. For a window, `eye` is where the ray
+    /// starts.
+    #[inline]
+    fn ray_dir(&self, eye: Vector, col: u32) -> Vector {
+        if let Some(window) = self.window {
+            return window.start + window.step * (col as f32 + 0.5) - eye;
+        }
+
+        let delta = self.half_width - col as f32;
+        match self.projection {
+            Projection::Rectilinear => self.camera_dir + self.camera_left * (delta / self.focal),
+            Projection::Cylindrical | Projection::Fisheye => {
+                let angle = delta / self.focal;
+                self.camera_dir.cmul(Vector(angle.cos(), angle.sin()))
+            }
+        }
+    }
+
+    /// The distance that scales a wall hit at `r` along `ray`. Rectilinear projection uses the
+    /// distance along the view direction; the angular projections use the true distance.
+    #[inline]
+    fn depth(&self, ray: Ray, r: f32) -> f32 {
+        if self.window.is_some() {
+            return r;
+        }
+
+        match self.projection {
+            Projection::Rectilinear => ray.dir.dot_product(self.camera_dir) * r,
+            Projection::Cylindrical | Projection::Fisheye => ray.dir.mag() * r,
+        }
+    }
+
+    /// How far the horizon moves down the screen, in pixels, for a pitch in degrees.
+    #[inline]
+    fn shear(&self, pitch: f32) -> f32 {
+        let pitch = pitch.to_radians();
+        match self.projection {
+            Projection::Fisheye => self.focal * pitch,
+            _ => self.focal * pitch.tan(),
+        }
+    }
+
+    /// The elevation angle, in degrees, of a line `dy` pixels above the horizon.
+    #[inline]
+    fn elevation(&self, dy: f32) -> f32 {
+        if let Some(window) = self.window {
+            return (dy / (self.focal * window.distance)).atan().to_degrees();
+        }
+
+        match self.projection {
+            Projection::Fisheye => (dy / self.focal).to_degrees(),
+            _ => (dy / self.focal).atan().to_degrees(),
+        }
+    }
+}
+
+/// The background prepared for a frame, so that drawing it is a table lookup per pixel.
+enum BackgroundRows<'a> {
+    Color(Color),
+    Sky {
+        sky: &'a Sky,
+        /// The sky texel row of each line above the horizon.
+        rows: Vec<u32>,
+    },
+}
+
+impl<'a> BackgroundRows<'a> {
+    fn new(background: &'a Background, lens: &Lens, center: f32, horizon: f32, rect: Rect) -> Self {
+        match background {
+            Background::Color(color) => BackgroundRows::Color(*color),
+            Background::Sky(sky) => {
+                let horizon = if sky.tilt { horizon } else { center };
+                let coverage = sky.coverage();
+                let sky_height = sky.image.height() as i32;
+
+                let rows = (0..rect.height)
+                    .take_while(|line| (*line as f32) < horizon)
+                    .map(|line| {
+                        let elevation = lens.elevation(horizon - line as f32);
+                        let v = 1.0 - elevation / coverage;
+                        ((v * sky_height as f32) as i32).clamp(0, sky_height - 1) as u32
+                    })
+                    .collect();
+
+                BackgroundRows::Sky { sky, rows }
+            }
+        }
+    }
+
+    #[inline]
+    fn draw(&self, image: &Image, rect: Rect, col: u32, ray: Ray, lines: Range<u32>) {
+        let x = rect.x + col;
+        match self {
+            BackgroundRows::Color(color) => {
+                for line in lines {
+                    image.set_unsafe(x, rect.y + line, *color);
+                }
+            }
+            BackgroundRows::Sky { sky, rows } => {
+                let sky_width = sky.image.width();
+                let u = 1.0 - ray.dir.angle() / 360.0;
+                let sky_x = ((u * sky_width as f32) as u32).min(sky_width - 1);
+
+                for line in lines {
+                    let color = match rows.get(line as usize) {
+                        Some(&y) => sky.image.get(sky_x, y),
+                        None => sky.ground,
+                    };
+                    image.set_unsafe(x, rect.y + line, color);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(value: f32, expected: f32) {
+        assert!(
+            (value - expected).abs() < 1e-3,
+            "Expected approximately {}, but got {}",
+            expected,
+            value
+        );
+    }
+
+    fn lens(projection: Projection) -> Lens {
+        let mut camera = Camera::default();
+        camera.projection = projection;
+        let rect = Rect {
+            x: 0,
+            y: 0,
+            width: 200,
+            height: 100,
+        };
+        Lens::new(&camera, rect)
+    }
+
+    #[test]
+    fn pitch_shears_the_horizon() {
+        // With a 90 degree field of view, looking 45 degrees up moves the horizon by half the width
+        for projection in [Projection::Rectilinear, Projection::Fisheye] {
+            let lens = lens(projection);
+            assert_close(lens.shear(0.0), 0.0);
+            assert_close(lens.shear(45.0), 100.0);
+            assert_close(lens.shear(-45.0), -100.0);
+            assert_close(lens.elevation(lens.shear(30.0)), 30.0);
+        }
+
+        // Rectilinear shearing grows faster than the angle
+        assert!(lens(Projection::Rectilinear).shear(60.0) > lens(Projection::Fisheye).shear(60.0));
+    }
+
+    #[test]
+    fn columns_cast_rays_across_the_field_of_view() {
+        for projection in [
+            Projection::Rectilinear,
+            Projection::Cylindrical,
+            Projection::Fisheye,
+        ] {
+            let lens = lens(projection);
+
+            // The middle column looks straight ahead and the edges 45 degrees to each side
+            let center = lens.ray_dir(Vector::ZERO, 100);
+            assert_close(center.angle(), 0.0);
+            assert_close(lens.ray_dir(Vector::ZERO, 0).angle(), 45.0);
+            assert_close(lens.ray_dir(Vector::ZERO, 200).angle(), 315.0);
+        }
+
+        // Rectilinear columns are evenly spaced on a line, angular ones evenly spaced by angle
+        let rectilinear = lens(Projection::Rectilinear);
+        assert_close(rectilinear.ray_dir(Vector::ZERO, 50).angle(), 26.565);
+        let cylindrical = lens(Projection::Cylindrical);
+        assert_close(cylindrical.ray_dir(Vector::ZERO, 50).angle(), 22.5);
+        assert_close(cylindrical.ray_dir(Vector::ZERO, 50).mag(), 1.0);
+    }
+
+    #[test]
+    fn fixed_aspect_views_are_letterboxed() {
+        let image = Image::new(200, 100);
+        let rect = Rect::from_viewport(Viewport::RIGHT_HALF, &image);
+        assert_eq!(
+            rect,
+            Rect {
+                x: 100,
+                y: 0,
+                width: 100,
+                height: 100
+            }
+        );
+
+        // Pillarboxed when the view is wider than the aspect, letterboxed when it's taller
+        let full = Rect::full(&image);
+        let square = Rect {
+            x: 50,
+            width: 100,
+            ..full
+        };
+        assert_eq!(full.fit(1.0), square);
+        let wide = Rect {
+            y: 25,
+            height: 50,
+            ..rect
+        };
+        assert_eq!(rect.fit(2.0), wide);
+    }
 }
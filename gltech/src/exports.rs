@@ -1,3 +1,4 @@
+pub use crate::assets::*;
 pub use crate::core::*;
 pub use crate::engine::*;
 pub use crate::imaging::*;
@@ -1,9 +1,9 @@
-use std::{alloc::Layout, sync::Arc};
+use std::{path::Path, sync::Arc};
 
 use crate::imaging::Color;
 
 pub struct Image {
-    buffer: Arc<Color>,
+    buffer: Arc<[Color]>,
     width: u32,
     height: u32,
     pub(crate) widthf: f32,
@@ -15,12 +15,7 @@ unsafe impl Sync for Image {}
 
 impl Image {
     pub fn new(width: u32, height: u32) -> Self {
-        let layout = Layout::array::<Color>((width * height) as usize).unwrap();
-
-        let buffer = unsafe {
-            let ptr = std::alloc::alloc(layout) as *mut Color;
-            Arc::from_raw(ptr)
-        };
+        let buffer = vec![Color::BLACK; (width * height) as usize].into();
 
         Self {
             buffer,
@@ -31,6 +26,19 @@ impl Image {
         }
     }
 
+    /// Decodes an image file in any format supported by the `image` crate, such as PNG, JPEG or
+    /// BMP.
+    pub fn decode(bytes: &[u8]) -> Result<Self, String> {
+        let decoded = ::image::load_from_memory(bytes)
+            .map_err(|e| e.to_string())?
+            .into_rgb8();
+        let result = Self::new(decoded.width(), decoded.height());
+        for (x, y, pixel) in decoded.enumerate_pixels() {
+            result.set_unsafe(x, y, Color::rgb(pixel[0], pixel[1], pixel[2]));
+        }
+        Ok(result)
+    }
+
     pub fn cheap_clone(&self) -> Self {
         Self {
             buffer: self.buffer.clone(),
@@ -41,6 +49,11 @@ impl Image {
         }
     }
 
+    /// Whether other images share this image's pixels.
+    pub(crate) fn shares_pixels(&self) -> bool {
+        Arc::strong_count(&self.buffer) > 1
+    }
+
     #[inline]
     pub(crate) fn byte_slice(&self) -> &[u8] {
         unsafe { std::slice::from_raw_parts(self.u8_buffer(), self.size()) }
@@ -65,19 +78,14 @@ impl Image {
         (self.width * self.height * 4) as usize
     }
 
-    #[inline]
-    pub(crate) fn u32_buffer(&self) -> *mut u32 {
-        self.buffer.as_ref() as *const Color as *mut u32
-    }
-
     #[inline]
     pub(crate) unsafe fn buffer(&self) -> *mut Color {
-        self.buffer.as_ref() as *const Color as *mut Color
+        self.buffer.as_ptr() as *mut Color
     }
 
     #[inline]
     pub(crate) fn u8_buffer(&self) -> *mut u8 {
-        self.buffer.as_ref() as *const Color as *mut u8
+        self.buffer.as_ptr() as *mut u8
     }
 
     #[inline]
@@ -113,13 +121,124 @@ impl Image {
     pub fn coordinates(&self) -> impl Iterator<Item = (u32, u32)> {
         (0..self.height).flat_map(move |y| (0..self.width).map(move |x| (x, y)))
     }
+
+    /// Returns a copy of the image with half the width and height, where each pixel is the average
+    /// of the 2x2 block it covers. Odd dimensions are rounded down, but never below 1.
+    pub fn downsample(&self) -> Image {
+        let result = Image::new((self.width / 2).max(1), (self.height / 2).max(1));
+        self.downsample_into(&result);
+        result
+    }
+
+    /// Like [`Image::downsample`], but writes into an existing image with the downsampled size.
+    pub(crate) fn downsample_into(&self, target: &Image) {
+        debug_assert_eq!(target.width, (self.width / 2).max(1));
+        debug_assert_eq!(target.height, (self.height / 2).max(1));
+
+        for (x, y) in target.coordinates() {
+            let x0 = (x * 2).min(self.width - 1);
+            let y0 = (y * 2).min(self.height - 1);
+            let x1 = (x0 + 1).min(self.width - 1);
+            let y1 = (y0 + 1).min(self.height - 1);
+
+            let samples = [
+                self.get(x0, y0),
+                self.get(x1, y0),
+                self.get(x0, y1),
+                self.get(x1, y1),
+            ];
+            let (mut r, mut g, mut b) = (0u32, 0u32, 0u32);
+            for color in samples {
+                r += color.r() as u32;
+                g += color.g() as u32;
+                b += color.b() as u32;
+            }
+
+            target.set_unsafe(
+                x,
+                y,
+                Color::rgb((r / 4) as u8, (g / 4) as u8, (b / 4) as u8),
+            );
+        }
+    }
+
+    /// Copies every pixel of an image with the same dimensions into this one.
+    pub(crate) fn copy_from(&self, source: &Image) {
+        assert_eq!(self.dimensions(), source.dimensions());
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                source.buffer(),
+                self.buffer(),
+                (self.width * self.height) as usize,
+            );
+        }
+    }
+
+    /// Copies every pixel of an image into this one, stretching it with nearest neighbor sampling
+    /// if the dimensions differ.
+    pub(crate) fn copy_stretched_from(&self, source: &Image) {
+        if self.dimensions() == source.dimensions() {
+            self.copy_from(source);
+            return;
+        }
+
+        for (x, y) in self.coordinates() {
+            let sx = (x as u64 * source.width as u64 / self.width as u64) as u32;
+            let sy = (y as u64 * source.height as u64 / self.height as u64) as u32;
+            self.set_unsafe(x, y, source.get(sx, sy));
+        }
+    }
+
+    /// Saves the image to a file. The format is inferred from the file extension.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        let mut rgb = Vec::with_capacity((self.width * self.height * 3) as usize);
+        for (x, y) in self.coordinates() {
+            let color = self.get(x, y);
+            rgb.extend_from_slice(&[color.r(), color.g(), color.b()]);
+        }
+
+        ::image::save_buffer(
+            path,
+            &rgb,
+            self.width,
+            self.height,
+            ::image::ExtendedColorType::Rgb8,
+        )
+        .map_err(|e| e.to_string())
+    }
 }
 
-// impl Drop for Image {
-//     fn drop(&mut self) {
-//         let layout = Layout::array::<Color>((self.width * self.height) as usize).unwrap();
-//         unsafe {
-//             std::alloc::dealloc(self.u8_buffer(), layout);
-//         }
-//     }
-// }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgb(color: Color) -> (u8, u8, u8) {
+        (color.r(), color.g(), color.b())
+    }
+
+    #[test]
+    fn downsample_averages_blocks() {
+        let image = Image::new(4, 2);
+        image.set(0, 0, Color::rgb(100, 0, 0));
+        image.set(1, 0, Color::rgb(200, 0, 0));
+        image.set(0, 1, Color::rgb(0, 40, 0));
+        image.set(3, 1, Color::rgb(0, 0, 255));
+
+        let half = image.downsample();
+        assert_eq!(half.dimensions(), (2, 1));
+        assert_eq!(rgb(half.get(0, 0)), (75, 10, 0));
+        assert_eq!(rgb(half.get(1, 0)), (0, 0, 63));
+    }
+
+    #[test]
+    fn downsample_odd_sizes() {
+        // The last column of an odd width is dropped, and sizes never reach zero
+        let image = Image::new(5, 1);
+        image.set(4, 0, Color::WHITE);
+        let half = image.downsample();
+        assert_eq!(half.dimensions(), (2, 1));
+        assert_eq!(rgb(half.get(1, 0)), (0, 0, 0));
+        assert_eq!(half.downsample().dimensions(), (1, 1));
+        assert_eq!(Image::new(1, 1).downsample().dimensions(), (1, 1));
+    }
+}
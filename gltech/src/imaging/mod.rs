@@ -1,7 +1,9 @@
 mod color;
 mod image;
+mod sampler;
 mod texture;
 
 pub use color::*;
 pub use image::*;
+pub use sampler::*;
 pub use texture::*;
@@ -0,0 +1,222 @@
+use std::time::Duration;
+
+use crate::imaging::{Color, MipmapMode, Texture};
+
+/// The maximum number of samples taken along the major axis by [`Filter::Anisotropic`].
+const MAX_ANISOTROPY: u32 = 8;
+
+/// How texels are filtered when a texture is sampled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Filter {
+    /// Take the closest texel.
+    Nearest,
+    /// Interpolate between the four closest texels.
+    Bilinear,
+    /// Pick the mip level from the shorter side of the footprint and average several bilinear
+    /// samples along its longer side. Keeps walls seen at grazing angles sharp.
+    Anisotropic,
+}
+
+/// How texture coordinates outside of `[0, 1)` are mapped back into the texture.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Wrap {
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+impl Wrap {
+    #[inline]
+    pub(crate) fn apply(self, texel: i32, size: u32) -> u32 {
+        let size = size as i32;
+        let texel = match self {
+            Wrap::Repeat => texel.rem_euclid(size),
+            Wrap::Clamp => texel.clamp(0, size - 1),
+            Wrap::Mirror => {
+                let texel = texel.rem_euclid(2 * size);
+                if texel >= size {
+                    2 * size - 1 - texel
+                } else {
+                    texel
+                }
+            }
+        };
+        texel as u32
+    }
+}
+
+/// Samples a texture with a fixed texel footprint at a fixed point in time, which also fixes the
+/// animation frame.
+///
+/// Mip level selection only depends on the footprint, so the renderer creates one sampler per
+/// column and reuses it for every pixel in that column.
+pub struct Sampler<'a> {
+    texture: &'a Texture,
+    filter: Filter,
+    frame: usize,
+    hoffset: f32,
+    voffset: f32,
+    level: usize,
+    next_level: usize,
+    blend: f32,
+    taps: u32,
+    tap_step: (f32, f32),
+}
+
+impl<'a> Sampler<'a> {
+    /// Creates a sampler for pixels that span `du` and `dv` in texture coordinates, where `1.0`
+    /// is the full width or height of the texture.
+    pub fn new(texture: &'a Texture, du: f32, dv: f32, filter: Filter, time: Duration) -> Self {
+        let source = texture.source();
+        let texels_u = (du * texture.hrepeat()).abs() * source.widthf;
+        let texels_v = (dv * texture.vrepeat()).abs() * source.heightf;
+
+        let (footprint, taps) = match filter {
+            Filter::Anisotropic => {
+                let minor = texels_u.min(texels_v);
+                let major = texels_u.max(texels_v);
+                let taps = (major / minor.max(1.0)).ceil();
+                (minor, taps.clamp(1.0, MAX_ANISOTROPY as f32) as u32)
+            }
+            _ => (texels_u.max(texels_v), 1),
+        };
+
+        // Taps are spread along the major axis
+        let tap_step = if texels_u > texels_v {
+            (du * texture.hrepeat() / taps as f32, 0.0)
+        } else {
+            (0.0, dv * texture.vrepeat() / taps as f32)
+        };
+
+        let lod = footprint.log2().max(0.0);
+        let max_level = texture.mip_levels() - 1;
+        let (level, blend) = match texture.mipmap() {
+            MipmapMode::None => (0, 0.0),
+            MipmapMode::Nearest => ((lod.round() as usize).min(max_level), 0.0),
+            MipmapMode::Linear => {
+                let level = (lod as usize).min(max_level);
+                (level, lod - level as f32)
+            }
+        };
+
+        let (hoffset, voffset) = texture.offsets_at(time);
+
+        Self {
+            texture,
+            filter,
+            frame: texture.frame_at(time),
+            hoffset,
+            voffset,
+            level,
+            next_level: (level + 1).min(max_level),
+            blend,
+            taps,
+            tap_step,
+        }
+    }
+
+    #[inline]
+    pub fn map(&self, u: f32, v: f32) -> Color {
+        let (u, v) = self.texture.transform(u, v, self.hoffset, self.voffset);
+        let near = self.map_level(self.level, u, v);
+        if self.blend <= 0.0 || self.next_level == self.level {
+            return near;
+        }
+        near.lerp(self.map_level(self.next_level, u, v), self.blend)
+    }
+
+    #[inline]
+    fn map_level(&self, level: usize, u: f32, v: f32) -> Color {
+        match self.filter {
+            Filter::Nearest => self.texture.sample_nearest(self.frame, level, u, v),
+            Filter::Bilinear => self.texture.sample_bilinear(self.frame, level, u, v),
+            Filter::Anisotropic => {
+                if self.taps == 1 {
+                    return self.texture.sample_bilinear(self.frame, level, u, v);
+                }
+
+                let (mut r, mut g, mut b) = (0u32, 0u32, 0u32);
+                let (step_u, step_v) = self.tap_step;
+                let offset = 0.5 * (self.taps - 1) as f32;
+                for tap in 0..self.taps {
+                    let tap = tap as f32 - offset;
+                    let color = self.texture.sample_bilinear(
+                        self.frame,
+                        level,
+                        u + step_u * tap,
+                        v + step_v * tap,
+                    );
+                    r += color.r() as u32;
+                    g += color.g() as u32;
+                    b += color.b() as u32;
+                }
+                Color::rgb(
+                    (r / self.taps) as u8,
+                    (g / self.taps) as u8,
+                    (b / self.taps) as u8,
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imaging::Image;
+
+    #[test]
+    fn wrap_repeat() {
+        assert_eq!(Wrap::Repeat.apply(0, 4), 0);
+        assert_eq!(Wrap::Repeat.apply(3, 4), 3);
+        assert_eq!(Wrap::Repeat.apply(4, 4), 0);
+        assert_eq!(Wrap::Repeat.apply(-1, 4), 3);
+    }
+
+    #[test]
+    fn wrap_clamp() {
+        assert_eq!(Wrap::Clamp.apply(-5, 4), 0);
+        assert_eq!(Wrap::Clamp.apply(2, 4), 2);
+        assert_eq!(Wrap::Clamp.apply(9, 4), 3);
+    }
+
+    #[test]
+    fn wrap_mirror() {
+        assert_eq!(Wrap::Mirror.apply(3, 4), 3);
+        assert_eq!(Wrap::Mirror.apply(4, 4), 3);
+        assert_eq!(Wrap::Mirror.apply(7, 4), 0);
+        assert_eq!(Wrap::Mirror.apply(8, 4), 0);
+        assert_eq!(Wrap::Mirror.apply(-1, 4), 0);
+        assert_eq!(Wrap::Mirror.apply(-2, 4), 1);
+    }
+
+    #[test]
+    fn bilinear_reaches_last_texel() {
+        let image = Image::new(4, 1);
+        for x in 0..4 {
+            image.set(x, 0, Color::rgb(x as u8 * 10, 0, 0));
+        }
+        let mut texture = Texture::new(image);
+        texture.set_wrap(Wrap::Clamp, Wrap::Clamp);
+        let (u, v) = texture.transform(3.5 / 4.0, 0.5, 0.0, 0.0);
+
+        // The center of the last texel must map to exactly that texel
+        assert_eq!(texture.sample_bilinear(0, 0, u, v).r(), 30);
+    }
+
+    #[test]
+    fn anisotropic_keeps_grazing_footprints_sharp() {
+        let mut texture = Texture::new(Image::new(64, 64));
+        texture.set_mipmap(MipmapMode::Linear);
+
+        // Eight texels wide and one tall, as on a wall seen at a grazing angle
+        let (du, dv) = (8.0 / 64.0, 1.0 / 64.0);
+        let trilinear = Sampler::new(&texture, du, dv, Filter::Bilinear, Duration::ZERO);
+        let anisotropic = Sampler::new(&texture, du, dv, Filter::Anisotropic, Duration::ZERO);
+
+        assert_eq!(trilinear.level, 3);
+        assert_eq!(anisotropic.level, 0);
+        assert_eq!(anisotropic.taps, 8);
+        assert_eq!(anisotropic.tap_step.1, 0.0);
+    }
+}
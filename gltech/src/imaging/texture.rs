@@ -1,24 +1,243 @@
-use crate::imaging::{Color, Image};
+use std::{sync::Arc, time::Duration};
+
+use crate::imaging::{Color, Filter, Image, Sampler, Wrap};
+use crate::world::Camera;
+
+/// How a texture picks between its mip levels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MipmapMode {
+    /// Always sample the full resolution source.
+    None,
+    /// Sample the level closest to the texel footprint.
+    Nearest,
+    /// Blend samples from the two levels around the texel footprint. Combined with
+    /// [`Filter::Bilinear`], this is trilinear filtering.
+    Linear,
+}
+
+/// How an animated texture plays its frames once it reaches the last one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Playback {
+    /// Start over from the first frame.
+    Loop,
+    /// Play the frames backwards, then forwards again.
+    PingPong,
+    /// Hold the last frame.
+    Once,
+}
+
+/// What a live texture shows. See [`Texture::live`].
+pub enum Feed {
+    /// The scene as seen from a camera, such as a security feed. The camera's viewport and aspect
+    /// ratio are ignored; the view always fills the texture.
+    Camera(Camera),
+    /// The scene reflected by the planes showing the texture, as seen from the main camera.
+    Mirror,
+}
+
+struct Live {
+    feed: Feed,
+    interval: u32,
+    /// The image the renderer draws into, copied into the texture once complete so that a view
+    /// showing its own texture never reads a half drawn frame.
+    scratch: Image,
+}
+
+struct Frame {
+    /// The mip chain, starting with the source image at level 0.
+    levels: Box<[Image]>,
+    duration: Duration,
+}
+
+impl Frame {
+    fn new(source: Image, duration: Duration) -> Self {
+        let mut levels = vec![source];
+        loop {
+            let last = levels.last().unwrap();
+            if last.width() == 1 && last.height() == 1 {
+                break;
+            }
+            levels.push(last.downsample());
+        }
+
+        Self {
+            levels: levels.into(),
+            duration,
+        }
+    }
+
+    fn rebuild_mips(&self) {
+        for pair in self.levels.windows(2) {
+            pair[0].downsample_into(&pair[1]);
+        }
+    }
+}
 
 pub struct Texture {
-    source: Image,
+    frames: Arc<[Frame]>,
+    playback: Playback,
     hoffset: f32,
     voffset: f32,
     hrepeat: f32,
     vrepeat: f32,
+    hscroll: f32,
+    vscroll: f32,
+    texel_density: Option<f32>,
+    mipmap: MipmapMode,
+    filter: Option<Filter>,
+    hwrap: Wrap,
+    vwrap: Wrap,
+    live: Option<Box<Live>>,
 }
 
 impl Texture {
     pub fn new(source: Image) -> Self {
+        Self::from_frames(vec![Frame::new(source, Duration::ZERO)], Playback::Loop)
+    }
+
+    /// Creates a texture that cycles through `frames`, each shown for its paired duration.
+    ///
+    /// Frames may differ in size, but mip levels are picked from the size of the first one.
+    ///
+    /// Animation is driven by the engine's clock, so planes using the texture animate without any
+    /// script ticking them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `frames` is empty.
+    pub fn animated(
+        frames: impl IntoIterator<Item = (Image, Duration)>,
+        playback: Playback,
+    ) -> Self {
+        let frames: Vec<_> = frames
+            .into_iter()
+            .map(|(image, duration)| Frame::new(image, duration))
+            .collect();
+        assert!(
+            !frames.is_empty(),
+            "An animated texture needs at least one frame"
+        );
+        Self::from_frames(frames, playback)
+    }
+
+    /// Creates a texture of the given size backed by a live render of the scene. The engine
+    /// redraws it every `interval` frames, before drawing the screen.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `interval` is zero.
+    pub fn live(feed: Feed, width: u32, height: u32, interval: u32) -> Self {
+        assert!(
+            interval > 0,
+            "A live texture needs a refresh interval of at least one frame"
+        );
+
+        let mut texture = Self::new(Image::new(width, height));
+        texture.live = Some(Box::new(Live {
+            feed,
+            interval,
+            scratch: Image::new(width, height),
+        }));
+        texture
+    }
+
+    fn from_frames(frames: Vec<Frame>, playback: Playback) -> Self {
         Self {
-            source,
+            frames: frames.into(),
+            playback,
             hoffset: 0.0,
             voffset: 0.0,
             hrepeat: 1.0,
             vrepeat: 1.0,
+            hscroll: 0.0,
+            vscroll: 0.0,
+            texel_density: None,
+            mipmap: MipmapMode::Nearest,
+            filter: None,
+            hwrap: Wrap::Repeat,
+            vwrap: Wrap::Repeat,
+            live: None,
         }
     }
 
+    /// Creates a texture that shares this texture's frames, so it is as cheap as
+    /// [`Image::cheap_clone`]. Live textures are copied without their feed and stay still.
+    pub fn cheap_clone(&self) -> Self {
+        Self {
+            frames: self.frames.clone(),
+            playback: self.playback,
+            hoffset: self.hoffset,
+            voffset: self.voffset,
+            hrepeat: self.hrepeat,
+            vrepeat: self.vrepeat,
+            hscroll: self.hscroll,
+            vscroll: self.vscroll,
+            texel_density: self.texel_density,
+            mipmap: self.mipmap,
+            filter: self.filter,
+            hwrap: self.hwrap,
+            vwrap: self.vwrap,
+            live: None,
+        }
+    }
+
+    /// Whether other textures share this texture's frames.
+    pub(crate) fn shares_frames(&self) -> bool {
+        Arc::strong_count(&self.frames) > 1
+    }
+
+    /// The full resolution image of the first frame.
+    #[inline]
+    pub fn source(&self) -> &Image {
+        &self.frames[0].levels[0]
+    }
+
+    #[inline]
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    #[inline]
+    pub fn playback(&self) -> Playback {
+        self.playback
+    }
+
+    #[inline]
+    pub fn set_playback(&mut self, playback: Playback) {
+        self.playback = playback;
+    }
+
+    /// The index of the frame shown at the given engine time.
+    pub fn frame_at(&self, time: Duration) -> usize {
+        let total: Duration = self.frames.iter().map(|frame| frame.duration).sum();
+        if self.frames.len() == 1 || total.is_zero() {
+            return 0;
+        }
+
+        let total_nanos = total.as_nanos();
+        let mut elapsed = match self.playback {
+            Playback::Loop => time.as_nanos() % total_nanos,
+            Playback::PingPong => {
+                let elapsed = time.as_nanos() % (2 * total_nanos);
+                if elapsed < total_nanos {
+                    elapsed
+                } else {
+                    2 * total_nanos - 1 - elapsed
+                }
+            }
+            Playback::Once => time.as_nanos().min(total_nanos - 1),
+        };
+
+        for (index, frame) in self.frames.iter().enumerate() {
+            let duration = frame.duration.as_nanos();
+            if elapsed < duration {
+                return index;
+            }
+            elapsed -= duration;
+        }
+        self.frames.len() - 1
+    }
+
     #[inline]
     pub fn hoffset(&self) -> f32 {
         self.hoffset
@@ -40,37 +259,355 @@ impl Texture {
     }
 
     #[inline]
-    pub fn map_nearest(&self, u: f32, v: f32) -> Color {
-        let x =
-            (self.source.widthf * (self.hrepeat * u + self.hoffset)) as u32 % self.source.width();
-        let y =
-            (self.source.heightf * (self.vrepeat * v + self.voffset)) as u32 % self.source.height();
+    pub fn set_hoffset(&mut self, hoffset: f32) {
+        self.hoffset = hoffset;
+    }
+
+    #[inline]
+    pub fn set_voffset(&mut self, voffset: f32) {
+        self.voffset = voffset;
+    }
+
+    #[inline]
+    pub fn set_hrepeat(&mut self, hrepeat: f32) {
+        self.hrepeat = hrepeat;
+    }
+
+    #[inline]
+    pub fn set_vrepeat(&mut self, vrepeat: f32) {
+        self.vrepeat = vrepeat;
+    }
+
+    #[inline]
+    pub fn hscroll(&self) -> f32 {
+        self.hscroll
+    }
+
+    #[inline]
+    pub fn vscroll(&self) -> f32 {
+        self.vscroll
+    }
+
+    /// Scrolls the texture by `hscroll` and `vscroll` texture widths and heights per second of
+    /// engine time, on top of the static offsets.
+    #[inline]
+    pub fn set_scroll(&mut self, hscroll: f32, vscroll: f32) {
+        self.hscroll = hscroll;
+        self.vscroll = vscroll;
+    }
+
+    #[inline]
+    pub fn texel_density(&self) -> Option<f32> {
+        self.texel_density
+    }
+
+    /// Sets how many texels cover one world unit. With a density set, the texture tiles across a
+    /// plane according to the plane's size instead of being stretched once over it.
+    #[inline]
+    pub fn set_texel_density(&mut self, texel_density: Option<f32>) {
+        self.texel_density = texel_density;
+    }
+
+    /// The factors that convert plane coordinates into texture coordinates for a plane of the
+    /// given size in world units.
+    #[inline]
+    pub fn uv_scale(&self, width: f32, height: f32) -> (f32, f32) {
+        match self.texel_density {
+            Some(density) => {
+                let source = self.source();
+                (
+                    width * density / source.widthf,
+                    height * density / source.heightf,
+                )
+            }
+            None => (1.0, 1.0),
+        }
+    }
+
+    /// The horizontal and vertical offsets at the given engine time, including scrolling.
+    #[inline]
+    pub fn offsets_at(&self, time: Duration) -> (f32, f32) {
+        let time = time.as_secs_f32();
+        (
+            // Keep the scrolled distance small to preserve precision. Two periods line up with
+            // every wrap mode, including mirroring.
+            self.hoffset + (self.hscroll * time).rem_euclid(2.0),
+            self.voffset + (self.vscroll * time).rem_euclid(2.0),
+        )
+    }
+
+    #[inline]
+    pub fn mipmap(&self) -> MipmapMode {
+        self.mipmap
+    }
+
+    #[inline]
+    pub fn set_mipmap(&mut self, mipmap: MipmapMode) {
+        self.mipmap = mipmap;
+    }
+
+    /// The filter this texture is sampled with, or `None` to use the scene's filter.
+    #[inline]
+    pub fn filter(&self) -> Option<Filter> {
+        self.filter
+    }
+
+    #[inline]
+    pub fn set_filter(&mut self, filter: Option<Filter>) {
+        self.filter = filter;
+    }
+
+    #[inline]
+    pub fn hwrap(&self) -> Wrap {
+        self.hwrap
+    }
+
+    #[inline]
+    pub fn vwrap(&self) -> Wrap {
+        self.vwrap
+    }
+
+    #[inline]
+    pub fn set_wrap(&mut self, hwrap: Wrap, vwrap: Wrap) {
+        self.hwrap = hwrap;
+        self.vwrap = vwrap;
+    }
+
+    /// What the texture shows if it is a live texture.
+    #[inline]
+    pub fn feed(&self) -> Option<&Feed> {
+        self.live.as_ref().map(|live| &live.feed)
+    }
 
-        self.source.get(x, y)
+    /// Gives access to a live texture's feed, for example to move its camera.
+    #[inline]
+    pub fn feed_mut(&mut self) -> Option<&mut Feed> {
+        self.live.as_mut().map(|live| &mut live.feed)
+    }
+
+    /// How many frames pass between redraws of a live texture.
+    #[inline]
+    pub fn refresh_interval(&self) -> Option<u32> {
+        self.live.as_ref().map(|live| live.interval)
+    }
+
+    #[inline]
+    pub fn set_refresh_interval(&mut self, interval: u32) {
+        if let Some(live) = &mut self.live {
+            live.interval = interval.max(1);
+        }
+    }
+
+    /// The feed and the image to draw it into, if this is a live texture due for a redraw on
+    /// the given frame.
+    pub(crate) fn refresh_due(&self, frame: u64) -> Option<(&Feed, &Image)> {
+        let live = self.live.as_ref()?;
+        frame
+            .is_multiple_of(live.interval as u64)
+            .then_some((&live.feed, &live.scratch))
+    }
+
+    /// Shows the image drawn for a live texture and rebuilds its mip chain.
+    pub(crate) fn present(&self) {
+        let Some(live) = &self.live else {
+            return;
+        };
+
+        self.frames[0].levels[0].copy_from(&live.scratch);
+        self.frames[0].rebuild_mips();
+    }
+
+    /// Rebuilds the mip chain of every frame after their source images changed in place.
+    pub(crate) fn rebuild_mips(&self) {
+        for frame in self.frames.iter() {
+            frame.rebuild_mips();
+        }
+    }
+
+    /// The number of levels in the mip chain of the first frame, including the source. Smaller
+    /// frames have fewer levels, and use their last one in place of the missing ones.
+    #[inline]
+    pub fn mip_levels(&self) -> usize {
+        self.frames[0].levels.len()
+    }
+
+    /// Creates a sampler for pixels that span `du` and `dv` in texture coordinates, at the given
+    /// engine time. The texture's own filter takes precedence over `default_filter`.
+    #[inline]
+    pub fn sampler(&self, du: f32, dv: f32, default_filter: Filter, time: Duration) -> Sampler<'_> {
+        Sampler::new(self, du, dv, self.filter.unwrap_or(default_filter), time)
+    }
+
+    #[inline]
+    pub fn map_nearest(&self, u: f32, v: f32) -> Color {
+        let (u, v) = self.transform(u, v, self.hoffset, self.voffset);
+        self.sample_nearest(0, 0, u, v)
     }
 
     #[inline]
     pub fn map_bilinear(&self, u: f32, v: f32) -> Color {
-        let wf = self.source.widthf - 1.0;
-        let hf = self.source.heightf - 1.0;
+        let (u, v) = self.transform(u, v, self.hoffset, self.voffset);
+        self.sample_bilinear(0, 0, u, v)
+    }
+
+    /// Applies repeat and offset to a texture coordinate.
+    #[inline]
+    pub(crate) fn transform(&self, u: f32, v: f32, hoffset: f32, voffset: f32) -> (f32, f32) {
+        (self.hrepeat * u + hoffset, self.vrepeat * v + voffset)
+    }
+
+    /// Samples the closest texel of a frame's mip level. `u` and `v` must already be transformed.
+    #[inline]
+    pub(crate) fn sample_nearest(&self, frame: usize, level: usize, u: f32, v: f32) -> Color {
+        let image = self.level(frame, level);
+        let (x, y) = (image.widthf * u, image.heightf * v);
+        let x = self.hwrap.apply(x.floor() as i32, image.width());
+        let y = self.vwrap.apply(y.floor() as i32, image.height());
+
+        image.get(x, y)
+    }
+
+    /// A frame's mip level, or its smallest level if the frame has fewer levels than the first.
+    #[inline]
+    fn level(&self, frame: usize, level: usize) -> &Image {
+        let levels = &self.frames[frame].levels;
+        &levels[level.min(levels.len() - 1)]
+    }
+
+    /// Interpolates the four closest texels of a frame's mip level. `u` and `v` must already be
+    /// transformed.
+    #[inline]
+    pub(crate) fn sample_bilinear(&self, frame: usize, level: usize, u: f32, v: f32) -> Color {
+        let image = self.level(frame, level);
+        let (x, y) = (image.widthf * u, image.heightf * v);
 
-        let x = wf * (self.hrepeat * u + self.hoffset) % wf;
-        let y = hf * (self.vrepeat * v + self.voffset) % hf;
+        // Texel centers lie at half-integer coordinates
+        let x = x - 0.5;
+        let y = y - 0.5;
+        let fx = x.floor();
+        let fy = y.floor();
+        let tx = x - fx;
+        let ty = y - fy;
 
-        let x0 = x as u32;
-        let y0 = y as u32;
+        let x0 = self.hwrap.apply(fx as i32, image.width());
+        let x1 = self.hwrap.apply(fx as i32 + 1, image.width());
+        let y0 = self.vwrap.apply(fy as i32, image.height());
+        let y1 = self.vwrap.apply(fy as i32 + 1, image.height());
 
-        let tx = x - x0 as f32;
-        let ty = y - y0 as f32;
+        let top = image.get(x0, y0).lerp(image.get(x1, y0), tx);
+        let bottom = image.get(x0, y1).lerp(image.get(x1, y1), tx);
 
-        let q11 = self.source.get(x0, y0);
-        let q21 = self.source.get(x0 + 1, y0);
-        let q12 = self.source.get(x0, y0 + 1);
-        let q22 = self.source.get(x0 + 1, y0 + 1);
+        top.lerp(bottom, ty)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::WALL_HEIGHT;
+
+    fn frames(count: usize) -> Vec<(Image, Duration)> {
+        (0..count)
+            .map(|_| (Image::new(1, 1), Duration::from_millis(100)))
+            .collect()
+    }
 
-        let top = q11.lerp(q21, tx);
-        let bottom = q12.lerp(q22, tx);
+    #[test]
+    fn frame_at_loop() {
+        let texture = Texture::animated(frames(3), Playback::Loop);
+        assert_eq!(texture.frame_at(Duration::from_millis(0)), 0);
+        assert_eq!(texture.frame_at(Duration::from_millis(150)), 1);
+        assert_eq!(texture.frame_at(Duration::from_millis(299)), 2);
+        assert_eq!(texture.frame_at(Duration::from_millis(300)), 0);
+    }
+
+    #[test]
+    fn frame_at_ping_pong() {
+        let texture = Texture::animated(frames(3), Playback::PingPong);
+        assert_eq!(texture.frame_at(Duration::from_millis(250)), 2);
+        assert_eq!(texture.frame_at(Duration::from_millis(350)), 2);
+        assert_eq!(texture.frame_at(Duration::from_millis(450)), 1);
+        assert_eq!(texture.frame_at(Duration::from_millis(550)), 0);
+        assert_eq!(texture.frame_at(Duration::from_millis(650)), 0);
+    }
+
+    #[test]
+    fn frame_at_once() {
+        let texture = Texture::animated(frames(3), Playback::Once);
+        assert_eq!(texture.frame_at(Duration::from_millis(150)), 1);
+        assert_eq!(texture.frame_at(Duration::from_secs(10)), 2);
+    }
+
+    #[test]
+    fn mip_chain_reaches_one_texel() {
+        assert_eq!(Texture::new(Image::new(16, 4)).mip_levels(), 5);
+        assert_eq!(Texture::new(Image::new(5, 3)).mip_levels(), 3);
+        assert_eq!(Texture::new(Image::new(1, 1)).mip_levels(), 1);
+
+        // Every level averages the one above it
+        let image = Image::new(2, 2);
+        image.set(0, 0, Color::rgb(200, 100, 40));
+        let texture = Texture::new(image);
+        assert_eq!(texture.frames[0].levels[1].get(0, 0).r(), 50);
+        assert_eq!(texture.frames[0].levels[1].get(0, 0).g(), 25);
+    }
+
+    #[test]
+    fn uv_scale_follows_texel_density() {
+        let mut texture = Texture::new(Image::new(64, 32));
+        assert_eq!(texture.texel_density(), None);
+        assert_eq!(texture.uv_scale(256.0, WALL_HEIGHT), (1.0, 1.0));
+
+        // Half a texel per unit tiles a 64 texel wide texture every 128 units
+        texture.set_texel_density(Some(0.5));
+        assert_eq!(texture.texel_density(), Some(0.5));
+        assert_eq!(texture.uv_scale(256.0, WALL_HEIGHT), (2.0, 1.5625));
+    }
+
+    #[test]
+    fn offsets_wrap_scrolling() {
+        let mut texture = Texture::new(Image::new(1, 1));
+        texture.set_hoffset(0.25);
+        texture.set_scroll(0.5, -0.5);
+        assert_eq!(texture.offsets_at(Duration::ZERO), (0.25, 0.0));
+
+        // Scrolled distances wrap every two periods, in both directions
+        assert_eq!(texture.offsets_at(Duration::from_secs(5)), (0.75, 1.5));
+        assert_eq!(texture.offsets_at(Duration::from_secs(3)), (1.75, 0.5));
+    }
+
+    #[test]
+    fn frames_of_different_sizes() {
+        let small = Image::new(2, 2);
+        small.set(0, 0, Color::RED);
+        let frames = [
+            (Image::new(16, 16), Duration::from_millis(100)),
+            (small, Duration::from_millis(100)),
+        ];
+        let texture = Texture::animated(frames, Playback::Loop);
+        assert_eq!(texture.mip_levels(), 5);
+
+        // The smallest level of the first frame is past the end of the second frame's chain
+        let (u, v) = texture.transform(0.25, 0.25, 0.0, 0.0);
+        for level in 0..texture.mip_levels() {
+            texture.sample_nearest(1, level, u, v);
+            texture.sample_bilinear(1, level, u, v);
+        }
+        assert_eq!(texture.sample_nearest(1, 0, u, v).r(), 255);
+    }
+
+    #[test]
+    fn live_texture_presents_scratch() {
+        let texture = Texture::live(Feed::Mirror, 2, 2, 3);
+        assert!(texture.refresh_due(1).is_none());
+
+        let (_, scratch) = texture.refresh_due(3).unwrap();
+        for (x, y) in scratch.coordinates() {
+            scratch.set(x, y, Color::rgb(200, 0, 0));
+        }
+        texture.present();
 
-        return top.lerp(bottom, ty);
+        assert_eq!(texture.source().get(1, 1).r(), 200);
+        assert_eq!(texture.frames[0].levels[1].get(0, 0).r(), 200);
     }
 }
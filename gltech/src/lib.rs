@@ -1,3 +1,4 @@
+pub mod assets;
 pub mod core;
 pub mod engine;
 pub mod imaging;
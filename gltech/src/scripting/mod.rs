@@ -1,3 +1,7 @@
+mod params;
+pub use params::*;
+mod registry;
+pub use registry::*;
 pub mod script;
 pub use script::*;
 mod system_context;
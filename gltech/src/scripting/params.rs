@@ -0,0 +1,73 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// A script parameter, as written in a map file.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Param {
+    Bool(bool),
+    Number(f64),
+    Text(String),
+}
+
+impl Param {
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Param::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f32(&self) -> Option<f32> {
+        match self {
+            Param::Number(value) => Some(*value as f32),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Param::Text(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Reads a number for the parameter called `name`, or explains what was expected.
+    pub fn read_f32(&self, name: &str) -> Result<f32, String> {
+        self.as_f32()
+            .ok_or_else(|| format!("\"{}\" should be a number, got {:?}", name, self))
+    }
+
+    /// Reads a non-negative whole number for the parameter called `name`, such as an index.
+    pub fn read_usize(&self, name: &str) -> Result<usize, String> {
+        match self {
+            Param::Number(value) if *value >= 0.0 && value.fract() == 0.0 => Ok(*value as usize),
+            _ => Err(format!(
+                "\"{}\" should be a non-negative whole number, got {:?}",
+                name, self
+            )),
+        }
+    }
+}
+
+impl From<bool> for Param {
+    fn from(value: bool) -> Self {
+        Param::Bool(value)
+    }
+}
+
+impl From<f32> for Param {
+    fn from(value: f32) -> Self {
+        Param::Number(value as f64)
+    }
+}
+
+impl From<&str> for Param {
+    fn from(value: &str) -> Self {
+        Param::Text(value.into())
+    }
+}
+
+/// Script parameters by name, sorted so they are saved in a stable order.
+pub type Params = BTreeMap<String, Param>;
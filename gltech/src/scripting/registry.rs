@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+
+use crate::{Params, Script, standard::*};
+
+type Factory = Box<dyn Fn(&Params) -> Result<Box<dyn Script>, String>>;
+
+/// Creates scripts by name, so map files and editors can attach them without knowing their types.
+///
+/// A factory builds a script from its parameters, as in `(name: "rotate", params: {"speed": 9})`,
+/// and reports a message for parameters it doesn't accept. [`ScriptRegistry::new`] comes with the
+/// standard scripts registered:
+///
+/// - `"q1_controller"`: [`Q1Controller`], configured by its public fields
+/// - `"flat_player_controller"`: [`FlatPlayerController`], configured by its public fields
+pub struct ScriptRegistry {
+    factories: HashMap<String, Factory>,
+}
+
+impl ScriptRegistry {
+    pub fn new() -> Self {
+        let mut registry = Self::empty();
+        registry
+            .register("q1_controller", |params| {
+                Ok(Box::new(Q1Controller::from_params(params)?))
+            })
+            .register("flat_player_controller", |params| {
+                Ok(Box::new(FlatPlayerController::from_params(params)?))
+            });
+        registry
+    }
+
+    /// A registry without the standard scripts.
+    pub fn empty() -> Self {
+        Self {
+            factories: HashMap::new(),
+        }
+    }
+
+    /// Registers a factory under `name`, replacing any factory already registered with it.
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        factory: impl Fn(&Params) -> Result<Box<dyn Script>, String> + 'static,
+    ) -> &mut Self {
+        self.factories.insert(name.into(), Box::new(factory));
+        self
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.factories.contains_key(name)
+    }
+
+    /// The registered names, in no particular order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.factories.keys().map(String::as_str)
+    }
+
+    pub fn create(&self, name: &str, params: &Params) -> Result<Box<dyn Script>, String> {
+        let factory = self
+            .factories
+            .get(name)
+            .ok_or_else(|| format!("No script is registered as \"{}\"", name))?;
+        factory(params)
+    }
+}
+
+impl Default for ScriptRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Param;
+
+    #[test]
+    fn creates_standard_scripts_from_params() {
+        let registry = ScriptRegistry::new();
+        let mut params = Params::new();
+        params.insert("max_speed".into(), Param::from(400.0));
+        params.insert("camera".into(), Param::from(1.0));
+        let script = registry.create("q1_controller", &params).unwrap();
+        assert!(script.name().ends_with("Q1Controller"));
+
+        params.insert("camera".into(), Param::from(0.5));
+        assert!(registry.create("q1_controller", &params).is_err());
+        params.insert("wings".into(), Param::from(true));
+        assert!(registry.create("flat_player_controller", &params).is_err());
+        assert!(registry.create("rotate", &Params::new()).is_err());
+        assert!(!ScriptRegistry::empty().contains("q1_controller"));
+    }
+}
@@ -1,6 +1,6 @@
 use std::time::Duration;
 
-use crate::{Scene, SystemContext, engine::Input, world::Entity};
+use crate::{Params, Scene, SystemContext, engine::Input, world::Entity};
 
 pub struct StartContext<'a> {
     pub system: &'a mut SystemContext,
@@ -28,4 +28,31 @@ pub trait Script {
     fn start(&mut self, ctx: StartContext);
     fn tick(&mut self, ctx: UpdateContext);
     fn end(&mut self, ctx: EndContext);
+
+    /// The name the profiler reports the script's timings under.
+    fn name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+
+    /// Opts the script into save games by returning itself, see [`SaveGame`](crate::SaveGame).
+    fn persist(&mut self) -> Option<&mut dyn Persist> {
+        None
+    }
+
+    /// Read-only view of [`persist`](Script::persist), scripts overriding one must override both.
+    fn persisted(&self) -> Option<&dyn Persist> {
+        None
+    }
+}
+
+/// Script state that is kept in save games.
+pub trait Persist {
+    /// Identifies the script in save files, so it must not change between builds. The name the
+    /// script is registered under in a [`ScriptRegistry`](crate::ScriptRegistry) is a good fit.
+    fn save_name(&self) -> &str;
+
+    fn save(&self) -> Params;
+
+    /// Restores state returned by [`save`](Self::save), or explains why it can't.
+    fn restore(&mut self, state: &Params) -> Result<(), String>;
 }
@@ -0,0 +1,43 @@
+use crate::{EndContext, Entity, Script, StartContext, UpdateContext, Vector};
+
+/// Turns a plane to face the main camera every frame, around the plane's center, so it looks the
+/// same from every side like a sprite.
+pub struct Billboard {
+    /// The width of the plane in world units.
+    pub width: f32,
+}
+
+impl Billboard {
+    pub fn new(width: f32) -> Self {
+        Self { width }
+    }
+
+    fn face_camera(&self, entity: &mut Entity, camera: Vector) {
+        let Some(plane) = entity.plane_mut() else {
+            return;
+        };
+
+        let center = plane.segment.start + plane.segment.dir / 2.0;
+        let mut forward = center - camera;
+        if forward.modularize() == 0.0 {
+            return;
+        }
+
+        // Run from the viewer's left to their right, so the texture isn't mirrored
+        let dir = Vector(forward.y(), -forward.x()) * self.width;
+        plane.segment.start = center - dir / 2.0;
+        plane.segment.dir = dir;
+    }
+}
+
+impl Script for Billboard {
+    fn start(&mut self, ctx: StartContext) {
+        self.face_camera(ctx.entity, ctx.scene.camera.pos());
+    }
+
+    fn tick(&mut self, ctx: UpdateContext) {
+        self.face_camera(ctx.entity, ctx.scene.camera.pos());
+    }
+
+    fn end(&mut self, _ctx: EndContext) {}
+}
@@ -1,5 +1,7 @@
+mod billboard;
 mod player_controller;
 mod q1_controller;
 mod utils;
+pub use billboard::*;
 pub use player_controller::*;
 pub use q1_controller::*;
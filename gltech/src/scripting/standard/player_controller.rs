@@ -1,11 +1,15 @@
 use sdl2::keyboard::Scancode;
 
-use crate::{EndContext, Input, Script, StartContext, UpdateContext, Vector};
+use crate::{EndContext, Input, Params, Script, StartContext, UpdateContext, Vector};
 
 pub struct FlatPlayerController {
     pub speed: f32,
     pub vertical_speed: f32,
     pub m_sensitivity: f32,
+    /// The index of the camera this controller drives, in the scene's render order. Every
+    /// controller reads the same keyboard and mouse, so split-screen cameras can't be driven by
+    /// independent players.
+    pub camera: usize,
 }
 
 impl Default for FlatPlayerController {
@@ -14,11 +18,37 @@ impl Default for FlatPlayerController {
             speed: 100.0,
             vertical_speed: 100.0,
             m_sensitivity: 2.2,
+            camera: 0,
         }
     }
 }
 
 impl FlatPlayerController {
+    /// Creates a controller with its public fields set from `params`, by field name. Fields that
+    /// aren't set keep their default value.
+    pub fn from_params(params: &Params) -> Result<Self, String> {
+        let mut controller = Self::default();
+        for (name, value) in params {
+            let field = match name.as_str() {
+                "speed" => &mut controller.speed,
+                "vertical_speed" => &mut controller.vertical_speed,
+                "m_sensitivity" => &mut controller.m_sensitivity,
+                "camera" => {
+                    controller.camera = value.read_usize(name)?;
+                    continue;
+                }
+                _ => {
+                    return Err(format!(
+                        "FlatPlayerController has no parameter \"{}\"",
+                        name
+                    ));
+                }
+            };
+            *field = value.read_f32(name)?;
+        }
+        Ok(controller)
+    }
+
     fn wish_dir(look_dir: Vector, input: Input) -> Vector {
         let mut dir = Vector::ZERO;
 
@@ -51,24 +81,31 @@ impl Script for FlatPlayerController {
     fn tick(&mut self, ctx: UpdateContext) {
         let delta_time = ctx.delta_time.as_secs_f32();
 
-        let wish_dir = Self::wish_dir(ctx.scene.camera.ray.dir, ctx.input.clone());
-        ctx.scene
-            .camera
-            .translate(wish_dir * self.speed * delta_time);
+        let Some(camera) = ctx.scene.camera(self.camera) else {
+            return;
+        };
+        let wish_dir = Self::wish_dir(camera.ray.dir, ctx.input.clone());
+        let delta = wish_dir * self.speed * delta_time;
+        let crossing = ctx.scene.portal_crossing(camera.pos(), delta);
+
+        let Some(camera) = ctx.scene.camera_mut(self.camera) else {
+            return;
+        };
+        camera.translate(delta);
+        if let Some(transform) = crossing {
+            camera.teleport(transform);
+        }
 
-        let mouse_delta = ctx.input.mouse_rel().0;
-        ctx.scene
-            .camera
-            .rotate(self.m_sensitivity * -0.022 * mouse_delta as f32);
+        let (mouse_dx, mouse_dy) = ctx.input.mouse_rel();
+        camera.rotate(self.m_sensitivity * -0.022 * mouse_dx as f32);
+        camera.tilt(self.m_sensitivity * -0.022 * mouse_dy as f32);
 
         if ctx.input.is_key_down(Scancode::Space) {
-            ctx.scene.camera.z =
-                f32::min(ctx.scene.camera.z + self.vertical_speed * delta_time, 1.0);
+            camera.z = f32::min(camera.z + self.vertical_speed * delta_time, 1.0);
         }
 
         if ctx.input.is_key_down(Scancode::LAlt) {
-            ctx.scene.camera.z =
-                f32::max(ctx.scene.camera.z - self.vertical_speed * delta_time, 0.0);
+            camera.z = f32::max(camera.z - self.vertical_speed * delta_time, 0.0);
         }
     }
 
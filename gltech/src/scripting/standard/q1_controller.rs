@@ -2,6 +2,8 @@ use sdl2::keyboard::Scancode;
 
 use crate::EndContext;
 use crate::Input;
+use crate::Params;
+use crate::Persist;
 use crate::Script;
 use crate::StartContext;
 use crate::UpdateContext;
@@ -17,6 +19,10 @@ pub struct Q1Controller {
     pub height: f32,
     pub max_speed: f32,
     pub stop_speed: f32,
+    /// The index of the camera this controller drives, in the scene's render order. Every
+    /// controller reads the same keyboard and mouse, so split-screen cameras can't be driven by
+    /// independent players.
+    pub camera: usize,
 
     velocity: Vector,
     z_speed: f32,
@@ -35,6 +41,7 @@ impl Default for Q1Controller {
             height: 46.0,
             max_speed: 320.0,
             stop_speed: 100.0,
+            camera: 0,
 
             velocity: Vector::ZERO,
             grounded: true,
@@ -44,9 +51,39 @@ impl Default for Q1Controller {
 }
 
 impl Q1Controller {
+    /// Creates a controller with its public fields set from `params`, by field name. Fields that
+    /// aren't set keep their default value.
+    pub fn from_params(params: &Params) -> Result<Self, String> {
+        let mut controller = Self::default();
+        for (name, value) in params {
+            let field = match name.as_str() {
+                "acceleration" => &mut controller.acceleration,
+                "air_acceleration" => &mut controller.air_acceleration,
+                "jump_speed" => &mut controller.jump_speed,
+                "gravity" => &mut controller.gravity,
+                "m_sensitivity" => &mut controller.m_sensitivity,
+                "friction" => &mut controller.friction,
+                "height" => &mut controller.height,
+                "max_speed" => &mut controller.max_speed,
+                "stop_speed" => &mut controller.stop_speed,
+                "camera" => {
+                    controller.camera = value.read_usize(name)?;
+                    continue;
+                }
+                _ => return Err(format!("Q1Controller has no parameter \"{}\"", name)),
+            };
+            *field = value.read_f32(name)?;
+        }
+        Ok(controller)
+    }
+
     /// Update horizontal velocity based on input
     fn update_velocity(&mut self, ctx: &UpdateContext) {
-        let wishdir = Self::wishdir(ctx.scene.camera.ray.dir, ctx.input.clone());
+        let Some(camera) = ctx.scene.camera(self.camera) else {
+            return;
+        };
+        let look_dir = camera.ray.dir;
+        let wishdir = Self::wishdir(look_dir, ctx.input.clone());
 
         if self.grounded {
             self.accelerate(ctx, wishdir, self.max_speed);
@@ -72,13 +109,16 @@ impl Q1Controller {
         }
 
         let delta_time = ctx.delta_time.as_secs_f32();
-        ctx.scene.camera.z += self.z_speed * delta_time;
-        if ctx.scene.camera.z < self.height {
-            ctx.scene.camera.z = self.height;
+        let Some(camera) = ctx.scene.camera_mut(self.camera) else {
+            return;
+        };
+        camera.z += self.z_speed * delta_time;
+        if camera.z < self.height {
+            camera.z = self.height;
             self.grounded = true;
             self.z_speed = 0.0;
-        } else if ctx.scene.camera.z > 100.0 {
-            ctx.scene.camera.z = 100.0;
+        } else if camera.z > 100.0 {
+            camera.z = 100.0;
             self.z_speed = 0.0;
         }
 
@@ -87,10 +127,12 @@ impl Q1Controller {
 
     /// Update the view direction based on mouse movement
     fn update_view(&mut self, ctx: &mut UpdateContext) {
-        let mouse_delta = ctx.input.mouse_rel().0;
-        ctx.scene
-            .camera
-            .rotate(self.m_sensitivity * -0.022 * mouse_delta as f32);
+        let (mouse_dx, mouse_dy) = ctx.input.mouse_rel();
+        let Some(camera) = ctx.scene.camera_mut(self.camera) else {
+            return;
+        };
+        camera.rotate(self.m_sensitivity * -0.022 * mouse_dx as f32);
+        camera.tilt(self.m_sensitivity * -0.022 * mouse_dy as f32);
     }
 
     /// Calculate the desired movement direction based on input
@@ -179,22 +221,79 @@ impl Q1Controller {
 
 impl Script for Q1Controller {
     fn start(&mut self, ctx: StartContext) {
-        ctx.scene.camera.z = self.height;
+        let Some(camera) = ctx.scene.camera_mut(self.camera) else {
+            eprintln!(
+                "Q1Controller drives camera {}, which doesn't exist",
+                self.camera
+            );
+            return;
+        };
+        camera.z = self.height;
         ctx.system.set_capture_mouse(true);
     }
 
     fn tick(&mut self, mut ctx: UpdateContext) {
+        // The camera can be removed after the controller starts
+        let Some(pos) = ctx.scene.camera(self.camera).map(|camera| camera.pos()) else {
+            return;
+        };
+
         self.update_view(&mut ctx);
 
         self.check_jump(&ctx);
         self.update_z(&mut ctx);
 
         self.update_velocity(&ctx);
-        ctx.scene
-            .camera
-            .ray
-            .translate(self.velocity * ctx.delta_time.as_secs_f32());
+        let delta = self.velocity * ctx.delta_time.as_secs_f32();
+        let crossing = ctx.scene.portal_crossing(pos, delta);
+
+        let Some(camera) = ctx.scene.camera_mut(self.camera) else {
+            return;
+        };
+        camera.translate(delta);
+        if let Some(transform) = crossing {
+            // Keep moving the same way relative to the portal
+            camera.teleport(transform);
+            self.velocity = transform.rotate(self.velocity);
+        }
     }
 
     fn end(&mut self, _ctx: EndContext) {}
+
+    fn persist(&mut self) -> Option<&mut dyn Persist> {
+        Some(self)
+    }
+
+    fn persisted(&self) -> Option<&dyn Persist> {
+        Some(self)
+    }
+}
+
+/// Saves the movement state, so a loaded game keeps falling or sliding where it was saved.
+impl Persist for Q1Controller {
+    fn save_name(&self) -> &str {
+        "q1_controller"
+    }
+
+    fn save(&self) -> Params {
+        Params::from([
+            ("velocity_x".into(), self.velocity.x().into()),
+            ("velocity_y".into(), self.velocity.y().into()),
+            ("z_speed".into(), self.z_speed.into()),
+            ("grounded".into(), self.grounded.into()),
+        ])
+    }
+
+    fn restore(&mut self, state: &Params) -> Result<(), String> {
+        let get = |name: &str| state.get(name).ok_or(format!("\"{}\" is missing", name));
+        self.velocity = Vector(
+            get("velocity_x")?.read_f32("velocity_x")?,
+            get("velocity_y")?.read_f32("velocity_y")?,
+        );
+        self.z_speed = get("z_speed")?.read_f32("z_speed")?;
+        self.grounded = get("grounded")?
+            .as_bool()
+            .ok_or("\"grounded\" should be a boolean")?;
+        Ok(())
+    }
 }
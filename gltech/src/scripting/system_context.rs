@@ -1,3 +1,7 @@
+use std::{path::PathBuf, time::Duration};
+
+use crate::{Assets, Effect, FrameStats, Profile, Scene};
+
 #[derive(Debug)]
 pub enum SysRequest {
     SetResolution(u32, u32),
@@ -5,17 +9,41 @@ pub enum SysRequest {
     SetCaptureMouse(bool),
     SetTitle(String),
     SetVSync(bool),
+    Screenshot(PathBuf),
+    StartCapture(PathBuf, u32),
+    StopCapture,
+    SetEffects(Vec<Effect>),
+    AddEffect(Effect),
+    SetPostProcessing(bool),
+    SetRenderScale(f32),
+    SetDynamicResolution(Option<Duration>),
+    SetMaxFps(Option<u32>),
+    SetBackgroundFps(Option<u32>),
+    SetProfiling(bool),
+    SetProfilerOverlay(bool),
+    ExportProfile(PathBuf),
 }
 
 pub struct SystemContext {
     requests: Vec<SysRequest>,
+    pub(crate) assets: Assets,
+    pub(crate) frame_stats: FrameStats,
+    pub(crate) profile: Option<Profile>,
+    /// Where script timings are collected while profiling.
+    pub(crate) script_times: Option<Vec<(&'static str, Duration)>>,
+    pub(crate) next_scene: Option<Scene>,
     pub(crate) exit: bool,
 }
 
 impl SystemContext {
-    pub(crate) fn new() -> Self {
+    pub(crate) fn new(assets: Assets) -> Self {
         Self {
             requests: Vec::new(),
+            assets,
+            frame_stats: FrameStats::default(),
+            profile: None,
+            script_times: None,
+            next_scene: None,
             exit: false,
         }
     }
@@ -40,6 +68,106 @@ impl SystemContext {
         self.requests.push(SysRequest::SetVSync(vsync));
     }
 
+    /// Saves the next presented frame to `path`. The format is inferred from the file extension.
+    pub fn screenshot(&mut self, path: impl Into<PathBuf>) {
+        self.requests.push(SysRequest::Screenshot(path.into()));
+    }
+
+    /// Starts dumping every presented frame into `dir` as a numbered image sequence.
+    ///
+    /// While capturing, scripts are updated with a fixed timestep of `1 / fps` seconds instead of
+    /// the real frame time, so the sequence plays back smoothly at `fps` regardless of how long
+    /// each frame took to render and save.
+    pub fn start_capture(&mut self, dir: impl Into<PathBuf>, fps: u32) {
+        self.requests
+            .push(SysRequest::StartCapture(dir.into(), fps));
+    }
+
+    pub fn stop_capture(&mut self) {
+        self.requests.push(SysRequest::StopCapture);
+    }
+
+    /// Replaces the post-processing effects applied to every frame.
+    pub fn set_effects(&mut self, effects: Vec<Effect>) {
+        self.requests.push(SysRequest::SetEffects(effects));
+    }
+
+    /// Appends an effect to the end of the post-processing chain.
+    pub fn add_effect(&mut self, effect: Effect) {
+        self.requests.push(SysRequest::AddEffect(effect));
+    }
+
+    pub fn clear_effects(&mut self) {
+        self.requests.push(SysRequest::SetEffects(Vec::new()));
+    }
+
+    /// Turns the whole post-processing chain on or off without losing its effects.
+    pub fn set_post_processing(&mut self, enabled: bool) {
+        self.requests.push(SysRequest::SetPostProcessing(enabled));
+    }
+
+    /// Sets the resolution the scene is rendered at, as a fraction of the window resolution.
+    pub fn set_render_scale(&mut self, scale: f32) {
+        self.requests.push(SysRequest::SetRenderScale(scale));
+    }
+
+    /// Enables or disables dynamic resolution. See [`GLTechContext::dynamic_resolution`].
+    ///
+    /// [`GLTechContext::dynamic_resolution`]: crate::GLTechContext::dynamic_resolution
+    pub fn set_dynamic_resolution(&mut self, target_frame_time: Option<Duration>) {
+        self.requests
+            .push(SysRequest::SetDynamicResolution(target_frame_time));
+    }
+
+    /// Caps the frame rate. `None` renders as fast as possible, or at the display rate with vsync.
+    pub fn set_max_fps(&mut self, max_fps: Option<u32>) {
+        self.requests.push(SysRequest::SetMaxFps(max_fps));
+    }
+
+    /// Caps the frame rate while the window is out of focus. `None` keeps the regular cap.
+    pub fn set_background_fps(&mut self, background_fps: Option<u32>) {
+        self.requests
+            .push(SysRequest::SetBackgroundFps(background_fps));
+    }
+
+    /// Frame time statistics over the latest frames.
+    pub fn frame_stats(&self) -> FrameStats {
+        self.frame_stats
+    }
+
+    /// Starts or stops timing the stages of the main loop and every script.
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.requests.push(SysRequest::SetProfiling(enabled));
+    }
+
+    /// Shows or hides a graph of recent frame times, split by stage. Only drawn while profiling.
+    pub fn set_profiler_overlay(&mut self, visible: bool) {
+        self.requests.push(SysRequest::SetProfilerOverlay(visible));
+    }
+
+    /// Writes the profiles of the latest frames to a CSV file, to track performance regressions.
+    pub fn export_profile(&mut self, path: impl Into<PathBuf>) {
+        self.requests.push(SysRequest::ExportProfile(path.into()));
+    }
+
+    /// The profile of the last complete frame, if profiling.
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    /// The engine's asset cache. See [`GLTechContext::assets`].
+    ///
+    /// [`GLTechContext::assets`]: crate::GLTechContext::assets
+    pub fn assets(&self) -> &Assets {
+        &self.assets
+    }
+
+    /// Replaces the running scene at the end of the frame. Once the old scene is dropped, cached
+    /// assets nothing else refers to are freed.
+    pub fn change_scene(&mut self, scene: Scene) {
+        self.next_scene = Some(scene);
+    }
+
     pub fn exit(&mut self) {
         self.exit = true;
     }
@@ -1,12 +1,64 @@
 use crate::prelude::*;
+use crate::world::Transform;
+
+/// How the camera maps the view onto the screen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Projection {
+    /// Standard perspective. Straight lines stay straight, but wide fields of view stretch the
+    /// edges of the screen.
+    Rectilinear,
+    /// Spreads columns evenly by angle, projecting onto a cylinder around the camera. Avoids the
+    /// stretching of rectilinear projection at wide fields of view, such as on ultrawide screens.
+    Cylindrical,
+    /// Spreads both columns and lines evenly by angle. Heavily distorted; meant for debugging.
+    Fisheye,
+}
+
+/// A rectangle of the output surface a camera renders to, in fractions of the surface size. `(0, 0)`
+/// is the top left corner and `(1, 1)` the bottom right.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Viewport {
+    pub const FULL: Viewport = Viewport::new(0.0, 0.0, 1.0, 1.0);
+    pub const LEFT_HALF: Viewport = Viewport::new(0.0, 0.0, 0.5, 1.0);
+    pub const RIGHT_HALF: Viewport = Viewport::new(0.5, 0.0, 0.5, 1.0);
+    pub const TOP_HALF: Viewport = Viewport::new(0.0, 0.0, 1.0, 0.5);
+    pub const BOTTOM_HALF: Viewport = Viewport::new(0.0, 0.5, 1.0, 0.5);
+
+    pub const fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+}
 
 pub struct Camera {
     pub ray: Ray,
     pub z: f32,
     pub fov: f32,
+    pub projection: Projection,
+    /// A fixed aspect ratio (width / height) for the view. The view is letterboxed or pillarboxed
+    /// to fit the screen. `None` fills the whole screen.
+    pub aspect: Option<f32>,
+    /// Where on the output surface the camera renders to.
+    pub viewport: Viewport,
+    pitch: f32,
 }
 
 impl Camera {
+    /// The furthest the camera can look up or down, in degrees. Pitch is rendered by shearing the
+    /// view vertically, which distorts noticeably past this point.
+    pub const MAX_PITCH: f32 = 60.0;
+
     #[inline]
     pub fn pos(&self) -> Vector {
         self.ray.start
@@ -54,6 +106,30 @@ impl Camera {
         let new_dir = dir.cmul(Vector::from_deg(angle));
         self.set_dir(new_dir);
     }
+
+    /// The vertical look angle in degrees. Positive values look up.
+    #[inline]
+    pub fn pitch(&self) -> f32 {
+        self.pitch
+    }
+
+    /// Sets the vertical look angle in degrees, clamped to [`Camera::MAX_PITCH`].
+    #[inline]
+    pub fn set_pitch(&mut self, pitch: f32) {
+        self.pitch = pitch.clamp(-Self::MAX_PITCH, Self::MAX_PITCH);
+    }
+
+    #[inline]
+    pub fn tilt(&mut self, angle: f32) {
+        self.set_pitch(self.pitch + angle);
+    }
+
+    /// Moves the camera by a transform, such as the one of a portal it walked through. The view
+    /// direction is rotated along, so the camera keeps looking the same way relative to the portal.
+    pub fn teleport(&mut self, transform: Transform) {
+        self.set_pos(transform.point(self.pos()));
+        self.set_dir(transform.rotate(self.dir()));
+    }
 }
 
 impl Default for Camera {
@@ -62,6 +138,26 @@ impl Default for Camera {
             ray: Ray::new(Vector::ZERO, Vector::FORWARD),
             z: 50.0,
             fov: 90.0,
+            projection: Projection::Rectilinear,
+            aspect: None,
+            viewport: Viewport::FULL,
+            pitch: 0.0,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pitch_is_clamped() {
+        let mut camera = Camera::default();
+        camera.set_pitch(90.0);
+        assert_eq!(camera.pitch(), Camera::MAX_PITCH);
+        camera.tilt(-30.0);
+        assert_eq!(camera.pitch(), 30.0);
+        camera.tilt(-200.0);
+        assert_eq!(camera.pitch(), -Camera::MAX_PITCH);
+    }
+}
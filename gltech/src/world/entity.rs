@@ -1,11 +1,11 @@
 use std::ptr::NonNull;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use crate::engine::Input;
 use crate::scripting::script::Script;
 use crate::world::Plane;
 use crate::world::empty::Empty;
-use crate::{StartContext, SystemContext, UpdateContext, prelude::*};
+use crate::{EndContext, StartContext, SystemContext, UpdateContext, prelude::*};
 
 pub(crate) enum EntityInner {
     Empty(Empty),
@@ -26,6 +26,29 @@ impl Entity {
         self.scripts.push(script);
     }
 
+    pub(crate) fn scripts(&self) -> impl Iterator<Item = &Box<dyn Script>> {
+        self.scripts.iter()
+    }
+
+    pub(crate) fn scripts_mut(&mut self) -> impl Iterator<Item = &mut Box<dyn Script>> {
+        self.scripts.iter_mut()
+    }
+
+    /// The plane this entity wraps, if it is one.
+    pub fn plane(&self) -> Option<&Plane> {
+        match self.inner {
+            EntityInner::Plane(ref plane) => Some(plane),
+            _ => None,
+        }
+    }
+
+    pub fn plane_mut(&mut self) -> Option<&mut Plane> {
+        match self.inner {
+            EntityInner::Plane(ref mut plane) => Some(plane),
+            _ => None,
+        }
+    }
+
     fn inner_pos(&self) -> Vector {
         match self.inner {
             EntityInner::Empty(ref empty) => empty.pos,
@@ -151,6 +174,21 @@ impl Entity {
         }
     }
 
+    pub(crate) fn end(&mut self, scene: &mut Scene, time: Duration, system: &mut SystemContext) {
+        let self_ptr = self as *mut Entity;
+        let scripts = self.scripts.iter_mut().collect::<Vec<_>>();
+        for script in scripts {
+            let ctx = EndContext {
+                entity: unsafe { &mut *self_ptr },
+                time,
+                system,
+                scene,
+            };
+
+            script.end(ctx);
+        }
+    }
+
     pub(crate) fn update(
         &mut self,
         scene: &mut Scene,
@@ -163,6 +201,8 @@ impl Entity {
         let self_ptr = self as *mut Entity;
         let scripts = self.scripts.iter_mut().collect::<Vec<_>>();
         for script in scripts {
+            // Time the script if the profiler is collecting script timings
+            let start = system.script_times.is_some().then(Instant::now);
             let ctx = UpdateContext {
                 entity: unsafe { &mut *self_ptr },
                 input: input.clone(),
@@ -173,6 +213,10 @@ impl Entity {
             };
 
             script.tick(ctx);
+
+            if let (Some(start), Some(times)) = (start, &mut system.script_times) {
+                times.push((script.name(), start.elapsed()));
+            }
         }
     }
 }
@@ -0,0 +1,330 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::{
+    Asset, AssetError, AssetResult, Assets, Color, Image, Plane, Scene, Texture, Vector,
+    WALL_HEIGHT,
+};
+
+/// A 2D grid of tile IDs, such as a Wolfenstein-style level. Row `0` is the top of the grid.
+///
+/// Tiles loaded from text are the characters' code points, so `'#'` is tile `'#' as u32`. Tiles
+/// loaded from images are the pixels' colors as `0xRRGGBB`, see [`TileGrid::color_tile`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct TileGrid {
+    width: usize,
+    height: usize,
+    tiles: Vec<u32>,
+}
+
+impl TileGrid {
+    /// Tiles outside of any palette are empty, but this one is used to fill new grids.
+    pub const EMPTY: u32 = ' ' as u32;
+
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            tiles: vec![Self::EMPTY; width * height],
+        }
+    }
+
+    /// Reads a grid with one character per tile and one line per row. Short lines are padded with
+    /// empty tiles.
+    pub fn parse_ascii(source: &str) -> Self {
+        let rows: Vec<&str> = source.lines().collect();
+        let width = rows
+            .iter()
+            .map(|row| row.chars().count())
+            .max()
+            .unwrap_or(0);
+
+        let mut grid = Self::new(width, rows.len());
+        for (y, row) in rows.iter().enumerate() {
+            for (x, tile) in row.chars().enumerate() {
+                grid.set(x, y, tile);
+            }
+        }
+        grid
+    }
+
+    /// Reads a grid with one pixel per tile.
+    pub fn from_image(image: &Image) -> Self {
+        let mut grid = Self::new(image.width() as usize, image.height() as usize);
+        for (x, y) in image.coordinates() {
+            grid.set(x as usize, y as usize, Self::color_tile(image.get(x, y)));
+        }
+        grid
+    }
+
+    /// The tile ID of a pixel color in grids read from images.
+    pub const fn color_tile(color: Color) -> u32 {
+        (color.r() as u32) << 16 | (color.g() as u32) << 8 | color.b() as u32
+    }
+
+    #[inline]
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    #[inline]
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Gets a tile, or `None` outside of the grid.
+    pub fn get(&self, x: usize, y: usize) -> Option<u32> {
+        (x < self.width && y < self.height).then(|| self.tiles[y * self.width + x])
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, tile: impl Into<u32>) {
+        assert!(x < self.width && y < self.height, "Tile is out of the grid");
+        self.tiles[y * self.width + x] = tile.into();
+    }
+
+    /// Finds the first tile with the given ID, scanning rows from the top.
+    pub fn find(&self, tile: impl Into<u32>) -> Option<(usize, usize)> {
+        let tile = tile.into();
+        let index = self.tiles.iter().position(|&t| t == tile)?;
+        Some((index % self.width, index / self.width))
+    }
+}
+
+/// Grids are read from images if the path has an image extension, and from text otherwise.
+impl Asset for TileGrid {
+    fn load(assets: &Assets, path: &str) -> AssetResult<Self> {
+        let bytes = assets.vfs().read(path)?;
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+
+        let grid = match extension.as_str() {
+            "png" | "bmp" | "gif" | "jpg" | "jpeg" | "tga" => {
+                Image::decode(&bytes).map(|image| Self::from_image(&image))
+            }
+            _ => String::from_utf8(bytes)
+                .map(|source| Self::parse_ascii(&source))
+                .map_err(|e| e.to_string()),
+        };
+        grid.map_err(|message| AssetError::Decode {
+            path: path.into(),
+            message,
+        })
+    }
+}
+
+/// Turns a [`TileGrid`] into walls. Tiles in the palette are solid walls, and every other tile is
+/// open floor.
+///
+/// Only the faces of walls that border open floor become planes, and neighbouring faces of the same
+/// tile along a straight line are merged into a single plane. The grid's edges are treated as
+/// solid. Merged planes tile their texture once per tile, unless the palette texture sets its own
+/// texel density.
+pub struct GridBuilder {
+    palette: HashMap<u32, Texture>,
+    tile_size: f32,
+    camera_marker: Option<u32>,
+}
+
+impl GridBuilder {
+    pub fn new() -> Self {
+        Self {
+            palette: HashMap::new(),
+            tile_size: WALL_HEIGHT,
+            camera_marker: None,
+        }
+    }
+
+    /// Makes `tile` a wall showing `texture`.
+    pub fn tile(&mut self, tile: impl Into<u32>, texture: Texture) -> &mut Self {
+        self.palette.insert(tile.into(), texture);
+        self
+    }
+
+    /// The side of a tile in world units. Defaults to 100, the height of a wall.
+    pub fn tile_size(&mut self, size: f32) -> &mut Self {
+        self.tile_size = size;
+        self
+    }
+
+    /// Places the camera at the center of the first tile with this ID.
+    pub fn camera_marker(&mut self, tile: impl Into<u32>) -> &mut Self {
+        self.camera_marker = Some(tile.into());
+        self
+    }
+
+    /// The world position of a tile's top left corner. Columns run along +x and rows along -y.
+    pub fn corner(&self, x: usize, y: usize) -> Vector {
+        Vector(x as f32, -(y as f32)) * self.tile_size
+    }
+
+    /// The world position of a tile's center.
+    pub fn center(&self, x: usize, y: usize) -> Vector {
+        self.corner(x, y) + Vector(0.5, -0.5) * self.tile_size
+    }
+
+    /// The camera's start position, if the grid has the camera marker.
+    pub fn camera_start(&self, grid: &TileGrid) -> Option<Vector> {
+        let (x, y) = grid.find(self.camera_marker?)?;
+        Some(self.center(x, y))
+    }
+
+    /// Creates a scene with the grid's walls, and the camera at the marker if there is one.
+    pub fn build(&self, grid: &TileGrid) -> Scene {
+        let mut scene = Scene::new();
+        if let Some(pos) = self.camera_start(grid) {
+            scene.camera.set_pos(pos);
+        }
+        for plane in self.planes(grid) {
+            scene.add(plane);
+        }
+        scene
+    }
+
+    /// The planes of every exposed wall face.
+    pub fn planes(&self, grid: &TileGrid) -> Vec<Plane> {
+        let mut planes = Vec::new();
+        for side in Side::ALL {
+            // Faces on the same line are merged, so walk each line along the face direction
+            let (lines, length) = match side {
+                Side::West | Side::East => (grid.width, grid.height),
+                Side::North | Side::South => (grid.height, grid.width),
+            };
+
+            for line in 0..lines {
+                let mut run: Option<(usize, u32)> = None;
+                for step in 0..=length {
+                    let (x, y) = match side {
+                        Side::West | Side::East => (line, step),
+                        Side::North | Side::South => (step, line),
+                    };
+                    let face = (step < length)
+                        .then(|| self.exposed_face(grid, x, y, side))
+                        .flatten();
+
+                    match run {
+                        Some((_, tile)) if face == Some(tile) => continue,
+                        Some((start, tile)) => {
+                            planes.push(self.face_plane(side, line, start, step - start, tile))
+                        }
+                        None => {}
+                    }
+                    run = face.map(|tile| (step, tile));
+                }
+            }
+        }
+        planes
+    }
+
+    fn is_wall(&self, tile: Option<u32>) -> bool {
+        // Outside of the grid counts as solid
+        tile.is_none_or(|tile| self.palette.contains_key(&tile))
+    }
+
+    /// The wall tile at `(x, y)` if its face on `side` borders open floor.
+    fn exposed_face(&self, grid: &TileGrid, x: usize, y: usize, side: Side) -> Option<u32> {
+        let tile = grid
+            .get(x, y)
+            .filter(|tile| self.palette.contains_key(tile))?;
+        let (dx, dy) = side.offset();
+        let neighbour = x
+            .checked_add_signed(dx)
+            .zip(y.checked_add_signed(dy))
+            .and_then(|(x, y)| grid.get(x, y));
+        (!self.is_wall(neighbour)).then_some(tile)
+    }
+
+    /// A plane covering `count` faces on `side` of the tiles along `line`, starting at `start`.
+    fn face_plane(&self, side: Side, line: usize, start: usize, count: usize, tile: u32) -> Plane {
+        let end = start + count;
+        // Planes run from the viewer's left to their right when seen from the open side, so
+        // textures aren't mirrored
+        let (from, to) = match side {
+            Side::West => (self.corner(line, start), self.corner(line, end)),
+            Side::East => (self.corner(line + 1, end), self.corner(line + 1, start)),
+            Side::North => (self.corner(end, line), self.corner(start, line)),
+            Side::South => (self.corner(start, line + 1), self.corner(end, line + 1)),
+        };
+
+        let mut texture = self.palette[&tile].cheap_clone();
+        if texture.texel_density().is_none() {
+            texture.set_texel_density(Some(texture.source().width() as f32 / self.tile_size));
+        }
+        Plane::new(from, to - from, texture)
+    }
+}
+
+impl Default for GridBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The side of a tile a wall face is on.
+#[derive(Clone, Copy)]
+enum Side {
+    West,
+    East,
+    North,
+    South,
+}
+
+impl Side {
+    const ALL: [Side; 4] = [Side::West, Side::East, Side::North, Side::South];
+
+    /// The grid offset of the neighbouring tile on this side.
+    fn offset(self) -> (isize, isize) {
+        match self {
+            Side::West => (-1, 0),
+            Side::East => (1, 0),
+            Side::North => (0, -1),
+            Side::South => (0, 1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn emits_merged_exposed_faces() {
+        let grid = TileGrid::parse_ascii(
+            "#####\n\
+             #   #\n\
+             # @ #\n\
+             #####",
+        );
+        let mut builder = GridBuilder::new();
+        builder
+            .tile('#', Texture::new(Image::new(4, 4)))
+            .camera_marker('@');
+
+        // One plane per side of the room, each spanning its whole length
+        let planes = builder.planes(&grid);
+        assert_eq!(planes.len(), 4);
+        let mut lengths: Vec<f32> = planes.iter().map(|p| p.segment.dir.mag()).collect();
+        lengths.sort_by(f32::total_cmp);
+        assert_eq!(lengths, vec![200.0, 200.0, 300.0, 300.0]);
+        let start = builder.camera_start(&grid).unwrap();
+        assert_eq!((start.x(), start.y()), (250.0, -250.0));
+
+        // A different tile in the middle of a wall splits it
+        let mut grid = grid;
+        grid.set(2, 0, 'X');
+        builder.tile('X', Texture::new(Image::new(4, 4)));
+        assert_eq!(builder.planes(&grid).len(), 6);
+    }
+
+    #[test]
+    fn reads_grids_from_images() {
+        let image = Image::new(2, 1);
+        image.set(1, 0, Color::RED);
+        let grid = TileGrid::from_image(&image);
+        assert_eq!(grid.get(1, 0), Some(0xFF0000));
+        assert_eq!(grid.get(0, 0), Some(TileGrid::color_tile(Color::BLACK)));
+        assert_eq!(grid.get(2, 0), None);
+    }
+}
@@ -1,10 +1,19 @@
 mod camera;
 mod empty;
 mod entity;
+mod grid;
 mod plane;
+mod portal;
+mod save;
 mod scene;
+mod sky;
 
 pub use camera::*;
+pub use empty::*;
 pub use entity::*;
+pub use grid::*;
 pub use plane::*;
+pub use portal::*;
+pub use save::*;
 pub use scene::*;
+pub use sky::*;
@@ -1,9 +1,17 @@
 use crate::imaging::Texture;
 use crate::prelude::*;
+use crate::world::Portal;
+
+/// The height of every plane in world units. Cameras see a plane's bottom edge at `z = 0` and its
+/// top edge at this height.
+pub const WALL_HEIGHT: f32 = 100.0;
 
 pub struct Plane {
     pub segment: Ray,
     pub texture: Texture,
+    /// Makes the plane a portal. Rays and players crossing it continue from the portal's
+    /// destination instead of stopping at the plane.
+    pub portal: Option<Portal>,
 }
 
 impl Plane {
@@ -11,6 +19,16 @@ impl Plane {
         Self {
             segment: Ray::new(start, dir),
             texture,
+            portal: None,
+        }
+    }
+
+    /// Creates a plane that acts as a portal to `destination`. The texture is only shown once a
+    /// ray has passed through [`MAX_PORTAL_DEPTH`](crate::world::MAX_PORTAL_DEPTH) portals.
+    pub fn portal(start: Vector, dir: Vector, destination: Ray, texture: Texture) -> Self {
+        Self {
+            portal: Some(Portal::new(destination)),
+            ..Self::new(start, dir, texture)
         }
     }
 
@@ -0,0 +1,168 @@
+use crate::{Plane, prelude::*};
+
+/// How many portals a single ray passes through. Past this, the renderer and raycasts treat a
+/// portal as a regular plane and show its texture.
+pub const MAX_PORTAL_DEPTH: u32 = 8;
+
+/// How far past a portal, in ray parameter units, a continued ray starts looking for hits. Keeps
+/// the ray from hitting the plane at the destination it leaves from.
+pub(crate) const PORTAL_EPSILON: f32 = 1e-3;
+
+/// Links a plane to a destination segment elsewhere in the world.
+///
+/// Anything crossing the plane at some point along it continues from the same point along the
+/// destination, rotated and scaled by the difference between the two segments. A ray crossing the
+/// plane from its left side leaves the destination towards the destination's right side, so two
+/// portals facing each other are linked with destinations pointing the same way as the other
+/// portal's plane.
+#[derive(Clone, Copy)]
+pub struct Portal {
+    pub destination: Ray,
+}
+
+impl Portal {
+    pub fn new(destination: Ray) -> Self {
+        Self { destination }
+    }
+
+    /// The transform from the plane at `source` into the space around the destination.
+    #[inline]
+    pub fn transform(&self, source: Ray) -> Transform {
+        let factor = self.destination.dir.cdiv(source.dir);
+        Transform {
+            offset: self.destination.start - source.start.cmul(factor),
+            factor,
+        }
+    }
+}
+
+/// A rotation, uniform scale and translation, such as the one applied by a [`Portal`].
+#[derive(Clone, Copy, Debug)]
+pub struct Transform {
+    offset: Vector,
+    /// Rotation and scale as a complex number.
+    factor: Vector,
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform {
+        offset: Vector::ZERO,
+        factor: Vector::FORWARD,
+    };
+
+    #[inline]
+    pub fn point(&self, point: Vector) -> Vector {
+        self.offset + point.cmul(self.factor)
+    }
+
+    /// Transforms a displacement, which is rotated and scaled but not translated.
+    #[inline]
+    pub fn vector(&self, vector: Vector) -> Vector {
+        vector.cmul(self.factor)
+    }
+
+    /// Rotates a vector without scaling it, for directions and velocities.
+    #[inline]
+    pub fn rotate(&self, vector: Vector) -> Vector {
+        vector.cmul(self.factor / self.factor.mag())
+    }
+
+    /// Transforms a ray. Distances along the ray keep their parameter, so a ray continued through
+    /// a portal reports hits at the same `r` it would have without the portal.
+    #[inline]
+    pub fn ray(&self, ray: Ray) -> Ray {
+        Ray::new(self.point(ray.start), self.vector(ray.dir))
+    }
+
+    /// Applies this transform, then `next`.
+    #[inline]
+    pub fn then(self, next: Transform) -> Transform {
+        Transform {
+            offset: next.point(self.offset),
+            factor: self.factor.cmul(next.factor),
+        }
+    }
+}
+
+/// A plane hit by a ray, with the ray's distance and the split along the plane.
+pub(crate) type Hit<'a> = (&'a Plane, (f32, f32));
+
+/// Follows a ray through portals to the plane it finally hits, if any. Also returns the transform
+/// of the portals passed through and how many times the ray was cast; hit distances are measured
+/// along the original ray.
+pub(crate) fn trace<'a>(
+    planes: &[&'a Plane],
+    ray: Ray,
+    near: f32,
+) -> (Option<Hit<'a>>, Transform, u32) {
+    let mut transform = Transform::IDENTITY;
+    let mut near = near;
+    for depth in 0..=MAX_PORTAL_DEPTH {
+        let Some((plane, (distance, split))) = nearest(planes, transform.ray(ray), near) else {
+            return (None, transform, depth + 1);
+        };
+        match plane.portal {
+            Some(portal) if depth < MAX_PORTAL_DEPTH => {
+                transform = transform.then(portal.transform(plane.segment));
+                near = distance + PORTAL_EPSILON;
+            }
+            _ => return (Some((plane, (distance, split))), transform, depth + 1),
+        }
+    }
+    unreachable!()
+}
+
+/// Finds the nearest plane hit by a ray, ignoring hits closer than `near`.
+pub(crate) fn nearest<'a>(planes: &[&'a Plane], ray: Ray, near: f32) -> Option<Hit<'a>> {
+    let mut rs = (f32::INFINITY, f32::INFINITY);
+    let mut nearest_plane = None;
+
+    for &plane in planes {
+        let (distance, split) = ray.get_rs(plane.segment);
+
+        if distance < near || !(0.0..1.0).contains(&split) {
+            continue;
+        };
+
+        if distance < rs.0 {
+            rs = (distance, split);
+            nearest_plane = Some(plane);
+        }
+    }
+
+    Some((nearest_plane?, rs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Vector, b: Vector) {
+        assert!((a - b).mag() < 1e-4, "{} is not close to {}", a, b);
+    }
+
+    #[test]
+    fn portal_maps_source_onto_destination() {
+        let source = Ray::new(Vector(0.0, 0.0), Vector(0.0, 10.0));
+        let portal = Portal::new(Ray::new(Vector(100.0, 50.0), Vector(-20.0, 0.0)));
+        let transform = portal.transform(source);
+
+        assert_close(transform.point(source.start), Vector(100.0, 50.0));
+        assert_close(transform.point(source.end()), Vector(80.0, 50.0));
+        assert_close(transform.rotate(Vector::FORWARD), Vector(0.0, 1.0));
+    }
+
+    #[test]
+    fn then_composes_in_order() {
+        let first = Portal::new(Ray::new(Vector(5.0, 0.0), Vector(0.0, 2.0)))
+            .transform(Ray::new(Vector::ZERO, Vector::FORWARD));
+        let second = Portal::new(Ray::new(Vector(-3.0, 1.0), Vector(1.0, 1.0)))
+            .transform(Ray::new(Vector(1.0, 1.0), Vector::LEFT));
+        let point = Vector(2.0, -7.0);
+
+        assert_close(
+            first.then(second).point(point),
+            second.point(first.point(point)),
+        );
+    }
+}
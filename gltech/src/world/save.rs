@@ -0,0 +1,356 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{Entity, Params, Persist, Scene};
+
+/// The version of the save file format. Files saved with another version can't be loaded.
+pub const SAVE_VERSION: u32 = 1;
+
+#[derive(Error, Debug)]
+pub enum SaveError {
+    #[error("Invalid save file: {0}")]
+    Parse(#[from] ron::error::SpannedError),
+
+    #[error("Failed to write save file: {0}")]
+    Write(#[from] ron::Error),
+
+    #[error("Failed to access save file: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Save file version {found} is not supported, expected version {expected}")]
+    Version { found: u32, expected: u32 },
+
+    #[error("Save file doesn't match the map: {0}")]
+    Mismatch(String),
+
+    #[error("Failed to restore the state of \"{script}\": {message}")]
+    State { script: String, message: String },
+}
+
+/// The dynamic state of a scene: its cameras, the transforms of its entities and the state of
+/// scripts that opt in through [`Script::persist`](crate::Script::persist).
+///
+/// A save game doesn't hold the scene itself. To load a game, load the map named by
+/// [`map`](Self::map) as usual, then [`restore`](Self::restore) the save game into it. Entities
+/// and scripts are matched by their order, so the map must be the one the game was saved on.
+/// Scripts that don't persist their state aren't saved.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SaveGame {
+    pub version: u32,
+    /// The map the game was saved on, such as its path in the asset filesystem.
+    pub map: String,
+    pub cameras: Vec<SavedCamera>,
+    pub entities: Vec<SavedEntity>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SavedCamera {
+    pub pos: (f32, f32),
+    pub dir: (f32, f32),
+    pub z: f32,
+    pub pitch: f32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SavedEntity {
+    pub pos: (f32, f32),
+    pub dir: (f32, f32),
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scripts: Vec<SavedScript>,
+}
+
+/// The state of a script that opts into save games.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SavedScript {
+    /// See [`Persist::save_name`].
+    pub name: String,
+    pub state: Params,
+}
+
+/// Just enough of a save file to check its version before reading the rest.
+#[derive(Deserialize)]
+struct Header {
+    version: u32,
+}
+
+impl SaveGame {
+    /// Captures the state of a scene loaded from `map`.
+    pub fn capture(scene: &Scene, map: impl Into<String>) -> Self {
+        let cameras = scene
+            .cameras()
+            .map(|camera| SavedCamera {
+                pos: camera.pos().into(),
+                dir: camera.dir().into(),
+                z: camera.z,
+                pitch: camera.pitch(),
+            })
+            .collect();
+
+        let entities = scene
+            .entities()
+            .map(|entity| SavedEntity {
+                pos: entity.pos().into(),
+                dir: entity.dir().into(),
+                scripts: persisted(entity)
+                    .map(|persist| SavedScript {
+                        name: persist.save_name().into(),
+                        state: persist.save(),
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        Self {
+            version: SAVE_VERSION,
+            map: map.into(),
+            cameras,
+            entities,
+        }
+    }
+
+    /// Restores the saved state into a freshly loaded copy of the map. If the scene doesn't match
+    /// the save game or a script rejects its state, the scene is left unchanged.
+    pub fn restore(&self, scene: &mut Scene) -> Result<(), SaveError> {
+        self.check(scene)?;
+        self.restore_scripts(scene)?;
+
+        let cameras = std::iter::once(&mut scene.camera).chain(scene.extra_cameras.iter_mut());
+        for (camera, saved) in cameras.zip(&self.cameras) {
+            camera.set_pos(saved.pos);
+            camera.set_dir(saved.dir);
+            camera.z = saved.z;
+            camera.set_pitch(saved.pitch);
+        }
+
+        for (entity, saved) in scene.entities_mut().zip(&self.entities) {
+            entity.set_pos(saved.pos);
+            entity.set_dir(saved.dir);
+        }
+        Ok(())
+    }
+
+    /// Restores the state of every persisting script, or none of them if one fails.
+    fn restore_scripts(&self, scene: &mut Scene) -> Result<(), SaveError> {
+        let saved = self.entities.iter().flat_map(|entity| &entity.scripts);
+        let mut scripts: Vec<_> = scene.entities_mut().flat_map(persisting).collect();
+        let previous: Vec<Params> = scripts.iter().map(|persist| persist.save()).collect();
+
+        for (index, (persist, saved)) in scripts.iter_mut().zip(saved).enumerate() {
+            if let Err(message) = persist.restore(&saved.state) {
+                // Scripts can fail halfway through, so roll back this one as well
+                for (persist, state) in scripts.iter_mut().zip(&previous).take(index + 1) {
+                    let _ = persist.restore(state);
+                }
+                return Err(SaveError::State {
+                    script: saved.name.clone(),
+                    message,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that a scene has the same cameras, entities and persisting scripts as the saved one.
+    fn check(&self, scene: &Scene) -> Result<(), SaveError> {
+        let mismatch = |message: String| Err(SaveError::Mismatch(message));
+
+        let cameras = scene.cameras().count();
+        if cameras != self.cameras.len() {
+            return mismatch(format!(
+                "{} cameras were saved, but the scene has {}",
+                self.cameras.len(),
+                cameras
+            ));
+        }
+
+        let entities = scene.entities().count();
+        if entities != self.entities.len() {
+            return mismatch(format!(
+                "{} entities were saved, but the scene has {}",
+                self.entities.len(),
+                entities
+            ));
+        }
+
+        for (index, (entity, saved)) in scene.entities().zip(&self.entities).enumerate() {
+            let names: Vec<&str> = persisted(entity).map(|p| p.save_name()).collect();
+            let saved_names: Vec<&str> = saved.scripts.iter().map(|s| s.name.as_str()).collect();
+            if names != saved_names {
+                return mismatch(format!(
+                    "entity {} was saved with scripts {:?}, but has {:?}",
+                    index, saved_names, names
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn parse(source: &str) -> Result<Self, SaveError> {
+        let header: Header = ron::from_str(source)?;
+        if header.version != SAVE_VERSION {
+            return Err(SaveError::Version {
+                found: header.version,
+                expected: SAVE_VERSION,
+            });
+        }
+        Ok(ron::from_str(source)?)
+    }
+
+    pub fn to_source(&self) -> Result<String, SaveError> {
+        let config = ron::ser::PrettyConfig::new().struct_names(false);
+        Ok(ron::ser::to_string_pretty(self, config)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SaveError> {
+        Ok(std::fs::write(path, self.to_source()?)?)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SaveError> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+}
+
+/// The scripts of an entity that opt into save games.
+fn persisted(entity: &Entity) -> impl Iterator<Item = &dyn Persist> {
+    entity.scripts().filter_map(|script| script.persisted())
+}
+
+/// Mutable access to [`persisted`], for restoring.
+fn persisting(entity: &mut Entity) -> impl Iterator<Item = &mut dyn Persist> {
+    entity.scripts_mut().filter_map(|script| script.persist())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Empty, EndContext, Param, Script, StartContext, UpdateContext, Vector};
+
+    #[derive(Default)]
+    struct Counter {
+        count: f32,
+    }
+
+    impl Script for Counter {
+        fn start(&mut self, _ctx: StartContext) {}
+        fn tick(&mut self, _ctx: UpdateContext) {}
+        fn end(&mut self, _ctx: EndContext) {}
+
+        fn persist(&mut self) -> Option<&mut dyn Persist> {
+            Some(self)
+        }
+
+        fn persisted(&self) -> Option<&dyn Persist> {
+            Some(self)
+        }
+    }
+
+    impl Persist for Counter {
+        fn save_name(&self) -> &str {
+            "counter"
+        }
+
+        fn save(&self) -> Params {
+            Params::from([("count".into(), self.count.into())])
+        }
+
+        fn restore(&mut self, state: &Params) -> Result<(), String> {
+            let count = state.get("count").ok_or("\"count\" is missing")?;
+            self.count = count.read_f32("count")?;
+            Ok(())
+        }
+    }
+
+    /// An empty carrying a counter.
+    fn counter() -> Entity {
+        let mut entity = Entity::from(Empty {
+            pos: Vector::ZERO,
+            dir: Vector::FORWARD,
+        });
+        entity.add_script(Box::new(Counter::default()));
+        entity
+    }
+
+    /// A freshly loaded map, with one counter.
+    fn map() -> Scene {
+        let mut scene = Scene::new();
+        scene.add(counter());
+        scene
+    }
+
+    #[test]
+    fn restores_into_a_fresh_map() {
+        let mut scene = map();
+        scene.camera.set_pos((10.0, 20.0));
+        scene.camera.z = 70.0;
+        let entity = scene.entities_mut().next().unwrap();
+        entity.set_pos((5.0, -5.0));
+        let state = Params::from([("count".into(), Param::from(3.0))]);
+        entity
+            .scripts_mut()
+            .next()
+            .unwrap()
+            .persist()
+            .unwrap()
+            .restore(&state)
+            .unwrap();
+
+        let saved = SaveGame::capture(&scene, "level.ron");
+        let saved = SaveGame::parse(&saved.to_source().unwrap()).unwrap();
+
+        let mut loaded = map();
+        saved.restore(&mut loaded).unwrap();
+        assert_eq!(SaveGame::capture(&loaded, "level.ron"), saved);
+        assert_eq!(loaded.camera.pos().y(), 20.0);
+        assert_eq!(loaded.camera.z, 70.0);
+    }
+
+    #[test]
+    fn rejects_mismatches() {
+        let saved = SaveGame::capture(&map(), "level.ron");
+
+        let source = saved
+            .to_source()
+            .unwrap()
+            .replace("version: 1", "version: 99");
+        assert!(matches!(
+            SaveGame::parse(&source),
+            Err(SaveError::Version { found: 99, .. })
+        ));
+
+        let mut other = map();
+        other.add(Empty {
+            pos: Vector::ZERO,
+            dir: Vector::FORWARD,
+        });
+        assert!(matches!(
+            saved.restore(&mut other),
+            Err(SaveError::Mismatch(_))
+        ));
+        assert!(matches!(
+            saved.restore(&mut Scene::new()),
+            Err(SaveError::Mismatch(_))
+        ));
+    }
+
+    #[test]
+    fn rejected_state_leaves_the_scene_unchanged() {
+        let mut scene = map();
+        scene.add(counter());
+        scene.camera.set_pos((10.0, 20.0));
+        let mut saved = SaveGame::capture(&scene, "level.ron");
+        assert_eq!(saved.entities[0].scripts[0].name, "counter");
+        saved.entities[0].scripts[0].state = Params::from([("count".into(), Param::from(3.0))]);
+        saved.entities[1].scripts[0].state = Params::from([("count".into(), Param::from("x"))]);
+
+        let mut loaded = map();
+        loaded.add(counter());
+        let before = SaveGame::capture(&loaded, "level.ron");
+        assert!(matches!(
+            saved.restore(&mut loaded),
+            Err(SaveError::State { .. })
+        ));
+        assert_eq!(SaveGame::capture(&loaded, "level.ron"), before);
+    }
+}
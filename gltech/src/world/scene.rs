@@ -1,11 +1,18 @@
 use std::time::Duration;
 
-use crate::{Ray, SystemContext, engine::Input, world::*};
+use crate::{Filter, Ray, SystemContext, Vector, engine::Input, world::*};
 
 // The Scene owns its entities and is responsible for dropping them when it goes out of scope. However, auxiliar structs
 // like planes are owned by entities and the Scene only holds references to them for rendering and collision detection.
 pub struct Scene {
+    /// The main camera.
     pub camera: Camera,
+    /// Additional cameras, each rendering to its own viewport after the main camera. Use them for
+    /// split-screen or picture-in-picture views.
+    pub extra_cameras: Vec<Camera>,
+    /// The filter used for textures that don't set their own.
+    pub filter: Filter,
+    pub background: Background,
     children: Vec<Entity>,
 }
 
@@ -13,15 +20,42 @@ impl Scene {
     pub fn new() -> Self {
         Self {
             camera: Camera::default().into(),
+            extra_cameras: Vec::new(),
+            filter: Filter::Nearest,
+            background: Background::default(),
             children: Vec::new(),
         }
     }
 
+    /// Iterates over every camera in render order: the main camera, then the extra cameras.
+    pub fn cameras(&self) -> impl Iterator<Item = &Camera> {
+        std::iter::once(&self.camera).chain(self.extra_cameras.iter())
+    }
+
+    /// Gets a camera by its index in render order, where `0` is the main camera.
+    pub fn camera(&self, index: usize) -> Option<&Camera> {
+        match index {
+            0 => Some(&self.camera),
+            _ => self.extra_cameras.get(index - 1),
+        }
+    }
+
+    pub fn camera_mut(&mut self, index: usize) -> Option<&mut Camera> {
+        match index {
+            0 => Some(&mut self.camera),
+            _ => self.extra_cameras.get_mut(index - 1),
+        }
+    }
+
     pub fn add(&mut self, node: impl Into<Entity>) {
         self.children.push(node.into());
     }
 
     // FIXME: Iterate the whole tree
+    pub fn entities(&self) -> impl Iterator<Item = &Entity> {
+        self.children.iter()
+    }
+
     pub fn entities_mut(&mut self) -> impl Iterator<Item = &mut Entity> {
         self.children.iter_mut()
     }
@@ -48,6 +82,16 @@ impl Scene {
         }
     }
 
+    /// Ends every script, before the scene is replaced by another one.
+    pub(crate) fn end(&mut self, system: &mut SystemContext, time: Duration) {
+        let ptr = self as *mut Scene;
+        let current_entities = self.entities_mut().collect::<Vec<_>>();
+        for entity in current_entities {
+            let second_ref = unsafe { &mut *ptr };
+            entity.end(second_ref, time, system);
+        }
+    }
+
     pub(crate) fn update(
         &mut self,
         input: Input,
@@ -63,23 +107,19 @@ impl Scene {
         }
     }
 
+    /// Finds the nearest plane hit by a ray, following it through portals. The distance is
+    /// measured along the original ray, including the parts before each portal.
     pub fn raycast(&self, ray: Ray) -> Option<(&Plane, (f32, f32))> {
-        let mut rs = (f32::INFINITY, f32::INFINITY);
-        let mut nearest_plane = None;
-
-        for plane in self.planes() {
-            let (distance, split) = ray.get_rs(plane.segment);
-
-            if distance < 0.0 || split < 0.0 || split >= 1.0 {
-                continue;
-            };
-
-            if distance < rs.0 {
-                rs = (distance, split);
-                nearest_plane = Some(plane);
-            }
-        }
+        let planes: Vec<&Plane> = self.planes().collect();
+        trace(&planes, ray, 0.0).0
+    }
 
-        Some((nearest_plane?, rs))
+    /// Finds the first portal crossed when moving from `start` by `delta`, and returns the
+    /// transform to apply to whatever moved through it.
+    pub fn portal_crossing(&self, start: Vector, delta: Vector) -> Option<Transform> {
+        let planes: Vec<&Plane> = self.planes().collect();
+        let (plane, (distance, _)) = nearest(&planes, Ray::new(start, delta), 0.0)?;
+        let portal = plane.portal?;
+        (distance < 1.0).then(|| portal.transform(plane.segment))
     }
 }
@@ -0,0 +1,43 @@
+use crate::imaging::{Color, Image};
+
+/// What the renderer draws wherever no plane covers the screen.
+pub enum Background {
+    Color(Color),
+    Sky(Sky),
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Background::Color(Color::BLACK)
+    }
+}
+
+/// A panoramic image wrapped around the camera.
+///
+/// The image covers 360 degrees horizontally and its bottom row lies on the horizon, so it scrolls
+/// as the camera rotates. Its vertical coverage follows from its aspect ratio to keep texels
+/// square; anything higher than that repeats the top row. Below the horizon, the sky is filled
+/// with the `ground` color.
+pub struct Sky {
+    pub image: Image,
+    pub ground: Color,
+    /// Whether the horizon follows the camera pitch. When disabled, the sky stays fixed on screen
+    /// as the camera looks up or down.
+    pub tilt: bool,
+}
+
+impl Sky {
+    pub fn new(image: Image) -> Self {
+        Self {
+            image,
+            ground: Color::BLACK,
+            tilt: true,
+        }
+    }
+
+    /// How many degrees above the horizon the image covers.
+    #[inline]
+    pub fn coverage(&self) -> f32 {
+        360.0 * self.image.heightf / self.image.widthf
+    }
+}
@@ -1,10 +1,5 @@
 extern crate gltech;
-extern crate image;
-extern crate thiserror;
-extern crate zip;
 
-mod file_system;
-mod images;
 mod map_pixel_benchmark;
 mod test_scene;
 
@@ -1,19 +1,17 @@
 use gltech::scripting::UpdateContext;
-use gltech::{prelude::*, Entity, Plane, Scene, Script, Texture};
-
-use crate::file_system::load_file_system;
+use gltech::{prelude::*, Assets, Entity, Plane, Scene, Script, Texture, Vfs};
 
 // This commit: 300 fps (map nearest)
 pub fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let file_system = load_file_system()?;
-    let bianca = file_system.get("bmp.bmp")?;
-    let image = crate::images::get_from_file(bianca)?;
+    let mut vfs = Vfs::new();
+    vfs.mount_packs_in("base", 0)?;
+    let assets = Assets::new(vfs);
+    let bianca = assets.load::<Texture>("bmp.bmp")?;
     let mut scene = Scene::new();
 
     // Plane
     {
-        let texture = Texture::new(image);
-        let plane = Plane::new(Vector(0.5, 1.0), Vector(0.0, -2.0), texture);
+        let plane = Plane::new(Vector(0.5, 1.0), Vector(0.0, -2.0), bianca.cheap_clone());
         let mut entity = Entity::from(plane);
         let script = BenchmarkScript::new();
         entity.add_script(Box::new(script));
@@ -25,7 +23,8 @@ pub fn main() -> Result<(), Box<dyn std::error::Error>> {
         .title("Map Pixel Benchmark")
         .resolution(1920, 1080)
         .fullscreen(true)
-        .vsync(false);
+        .vsync(false)
+        .assets(assets);
     engine.launch(scene)?;
 
     Ok(())
@@ -1,42 +1,81 @@
+mod loading_script;
 mod rotate_script;
 
-use crate::{file_system, images, test_scene::rotate_script::RotateScript};
+use crate::test_scene::{loading_script::LoadingScript, rotate_script::RotateScript};
 use gltech::{
     engine,
     prelude::*,
     standard::Q1Controller,
     world::{Entity, Plane},
-    Texture,
+    Assets, Color, Image, Texture, Vfs,
 };
+use std::path::Path;
 
 pub fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let file_system = file_system::load_file_system()?;
-    let bianca = file_system.get("bianca.jpg")?;
-    let image = images::get_from_file(bianca)?;
+    let mut vfs = Vfs::new();
+    vfs.mount_packs_in("base", 0)?;
+    // Loose files override the packs and are hot reloaded. They live apart from the packs, so the
+    // packs themselves aren't mounted as files and polled.
+    if Path::new("base/loose").is_dir() {
+        vfs.mount_dir("base/loose", 1)?;
+    }
+    let assets = Assets::new(vfs);
+
+    // Show a progress bar while the textures load in the background
+    let loading = assets.batch().load::<Texture>("bianca.jpg").start();
+    let mut loading_scene = Scene::new();
+    {
+        let bar = Image::new(1, 1);
+        bar.set(0, 0, Color::WHITE);
+        let primitive = Plane::new(Vector(100.0, 50.0), Vector::RIGHT, Texture::new(bar));
+        let mut entity = Entity::from(primitive);
+        entity.add_script(Box::new(LoadingScript {
+            loading,
+            next: build_scene,
+        }));
+        loading_scene.add(entity);
+    }
+
+    let mut engine = engine::init()?;
+
+    engine
+        .fullscreen(false)
+        .title("GLTech 3")
+        .vsync(false)
+        .assets(assets)
+        .hot_reload(cfg!(debug_assertions));
+    engine.launch(loading_scene)?;
+
+    Ok(())
+}
+
+fn build_scene(assets: &Assets) -> Result<Scene, Box<dyn std::error::Error>> {
+    let bianca = assets.load::<Texture>("bianca.jpg")?;
     let mut scene = Scene::new();
 
     // Rotating plane 1
     {
-        let texture = Texture::new(image.cheap_clone());
-        let primitive = Plane::new(Vector(100.0, -25.0), Vector(100.0, 0.0), texture);
+        let primitive = Plane::new(
+            Vector(100.0, -25.0),
+            Vector(100.0, 0.0),
+            bianca.cheap_clone(),
+        );
         let mut entity = Entity::from(primitive);
         entity.add_script(Box::new(RotateScript));
         scene.add(entity);
     }
     // Rotating plane 2
     {
-        let texture = Texture::new(image);
-        let primitive = Plane::new(Vector(100.0, 25.0), Vector(100.0, 0.0), texture);
+        let primitive = Plane::new(
+            Vector(100.0, 25.0),
+            Vector(100.0, 0.0),
+            bianca.cheap_clone(),
+        );
         let mut entity = Entity::from(primitive);
         entity.add_script(Box::new(RotateScript));
         entity.add_script(Box::new(Q1Controller::default()));
         scene.add(entity);
     }
 
-    let mut engine = engine::init()?;
-
-    engine.fullscreen(false).title("GLTech 3").vsync(false);
-    engine.launch(scene)?;
-
-    Ok(())
+    Ok(scene)
 }
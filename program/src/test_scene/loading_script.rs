@@ -0,0 +1,36 @@
+use gltech::{Assets, EndContext, Loading, Scene, Script, StartContext, UpdateContext, Vector};
+
+/// Stretches its plane into a progress bar while assets load, then switches to the scene built by
+/// `next`.
+pub struct LoadingScript {
+    pub loading: Loading,
+    pub next: fn(&Assets) -> Result<Scene, Box<dyn std::error::Error>>,
+}
+
+impl Script for LoadingScript {
+    fn start(&mut self, _ctx: StartContext) {}
+
+    fn tick(&mut self, ctx: UpdateContext) {
+        if let Some(plane) = ctx.entity.plane_mut() {
+            let progress = self.loading.progress().max(0.01);
+            plane.segment.dir = Vector::RIGHT * 100.0 * progress;
+        }
+
+        if !self.loading.is_done() {
+            return;
+        }
+
+        for error in self.loading.take_errors() {
+            eprintln!("Error: {}", error);
+        }
+        match (self.next)(ctx.system.assets()) {
+            Ok(scene) => ctx.system.change_scene(scene),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                ctx.system.exit();
+            }
+        }
+    }
+
+    fn end(&mut self, _ctx: EndContext) {}
+}